@@ -0,0 +1,101 @@
+use apollo_cw_asset::AssetList;
+use cosmwasm_std::{to_json_binary, DepsMut, Event, MessageInfo, Response};
+
+use crate::fees::BPS_DENOMINATOR;
+use crate::msg::ConfigUpdates;
+use crate::state::{
+    FEE_CONFIG, LIQUIDITY_HELPER, MAX_REFERRAL_FEE_BPS, ORACLE_CONFIG, RESERVED_BALANCES, ROUTER,
+};
+use crate::ContractError;
+
+#[cfg(feature = "astroport")]
+use crate::state::ASTROPORT_LIQUIDITY_MANAGER;
+
+pub fn execute_update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    updates: ConfigUpdates,
+) -> Result<Response, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    let event = apply_config_updates(deps, updates)?;
+
+    Ok(Response::new().add_event(event))
+}
+
+/// Sends `assets` held by the contract to `recipient`, or to the owner if not
+/// set. Assets of an operation in progress, which are only held by the contract
+/// while its reserved balances are set, can not be swept.
+pub fn execute_sweep(
+    deps: DepsMut,
+    info: MessageInfo,
+    assets: AssetList,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    if RESERVED_BALANCES.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::OperationInProgress {});
+    }
+
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
+
+    let event = Event::new("apollo/vault-zapper/execute_sweep")
+        .add_attribute("sender", &info.sender)
+        .add_attribute("recipient", &recipient)
+        .add_attribute("assets", to_json_binary(&assets)?.to_string());
+
+    Ok(Response::new()
+        .add_messages(assets.transfer_msgs(&recipient)?)
+        .add_event(event))
+}
+
+/// Validates and stores the given config updates. Returns an event with the
+/// updated values.
+pub fn apply_config_updates(deps: DepsMut, updates: ConfigUpdates) -> Result<Event, ContractError> {
+    let mut event = Event::new("apollo/vault-zapper/update_config");
+
+    if let Some(router) = updates.router {
+        let router = router.check(deps.api)?;
+        event = event.add_attribute("router", router.addr());
+        ROUTER.save(deps.storage, &router)?;
+    }
+
+    if let Some(liquidity_helper) = updates.liquidity_helper {
+        let liquidity_helper = liquidity_helper.check(deps.api)?;
+        event = event.add_attribute("liquidity_helper", liquidity_helper.addr());
+        LIQUIDITY_HELPER.save(deps.storage, &liquidity_helper)?;
+    }
+
+    if let Some(fee_config) = updates.fee_config {
+        let fee_config = fee_config.check(deps.as_ref())?;
+        event = event.add_attribute("fee_config", to_json_binary(&fee_config)?.to_string());
+        FEE_CONFIG.save(deps.storage, &fee_config)?;
+    }
+
+    if let Some(max_referral_fee_bps) = updates.max_referral_fee_bps {
+        if max_referral_fee_bps >= BPS_DENOMINATOR {
+            return Err(ContractError::Generic(
+                "Max referral fee must be less than 100%".to_string(),
+            ));
+        }
+        event = event.add_attribute("max_referral_fee_bps", max_referral_fee_bps.to_string());
+        MAX_REFERRAL_FEE_BPS.save(deps.storage, &max_referral_fee_bps)?;
+    }
+
+    if let Some(oracle_config) = updates.oracle_config {
+        let oracle_config = oracle_config.check(deps.as_ref())?;
+        event = event.add_attribute("oracle_config", to_json_binary(&oracle_config)?.to_string());
+        ORACLE_CONFIG.save(deps.storage, &oracle_config)?;
+    }
+
+    #[cfg(feature = "astroport")]
+    if let Some(astroport_liquidity_manager) = updates.astroport_liquidity_manager {
+        let astroport_liquidity_manager = deps.api.addr_validate(&astroport_liquidity_manager)?;
+        event = event.add_attribute("astroport_liquidity_manager", &astroport_liquidity_manager);
+        ASTROPORT_LIQUIDITY_MANAGER.save(deps.storage, &astroport_liquidity_manager)?;
+    }
+
+    Ok(event)
+}
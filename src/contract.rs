@@ -1,28 +1,43 @@
+use apollo_cw_asset::{Asset, AssetList};
 use apollo_utils::submessages::{find_event, parse_attribute_value};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
+    from_json, to_json_binary, Binary, Deps, DepsMut, Env, Event, MessageInfo, Reply, Response,
+    StdError, StdResult,
 };
-use cw2::set_contract_version;
+use cw2::{ensure_from_older_version, set_contract_version};
 use cw_vault_standard::extensions::lockup::{
     UNLOCKING_POSITION_ATTR_KEY, UNLOCKING_POSITION_CREATED_EVENT_TYPE,
 };
 
+use crate::config::{apply_config_updates, execute_sweep, execute_update_config};
 use crate::deposit::{
-    callback_deposit, callback_enforce_min_out, callback_provide_liquidity, execute_deposit,
+    callback_batch_deposit, callback_deposit, callback_enforce_min_out,
+    callback_provide_inner_liquidity, callback_provide_liquidity, callback_return_leftovers,
+    deposit, deposit_touched_assets, execute_batch_deposit, execute_deposit,
 };
 use crate::error::ContractError;
-use crate::lockup::execute_unlock;
-use crate::msg::{CallbackMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::fees::Fees;
+use crate::helpers::{base_token_assets, reserve_balances};
+use crate::lockup::{execute_transfer_unlocking_position, execute_unlock, unlock};
+use crate::msg::{CallbackMsg, Cw20HookMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 use crate::query::{
-    query_all_user_unlocking_positions, query_depositable_assets, query_receive_choices,
-    query_user_unlocking_positions_for_vault,
+    query_all_user_unlocking_positions, query_annotated_user_unlocking_positions, query_config,
+    query_contract_balances, query_depositable_assets, query_receive_choices,
+    query_simulate_deposit, query_simulate_redeem, query_user_unlocking_positions_for_vault,
+};
+use crate::state::{
+    FEE_CONFIG, LIQUIDITY_HELPER, LOCKUP_IDS, ORACLE_CONFIG, ROUTER, TEMP_LOCK_KEY,
+};
+use crate::vault_migration::{
+    callback_deposit_balances, callback_zap_into_vault, execute_migrate_between_vaults,
+    execute_withdraw_unlocked_to_vault, migrate_between_vaults,
 };
-use crate::state::{LIQUIDITY_HELPER, LOCKUP_IDS, ROUTER, TEMP_LOCK_KEY};
 use crate::withdraw::{
-    callback_after_redeem, callback_after_withdraw_liq, execute_redeem, execute_withdraw_unlocked,
-    execute_zap_base_tokens,
+    callback_after_redeem, callback_after_withdraw_liq, execute_batch_redeem, execute_redeem,
+    execute_withdraw_all_matured, execute_withdraw_unlocked, execute_withdraw_unlocked_many,
+    execute_zap_base_tokens, redeem, withdraw_touched_assets, zap_base_tokens,
 };
 
 #[cfg(feature = "astroport")]
@@ -40,6 +55,7 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    cw_ownable::initialize_owner(deps.storage, deps.api, Some(&msg.owner))?;
 
     ROUTER.save(deps.storage, &msg.router.check(deps.api)?)?;
     LIQUIDITY_HELPER.save(deps.storage, &msg.liquidity_helper.check(deps.api)?)?;
@@ -50,12 +66,17 @@ pub fn instantiate(
         &deps.api.addr_validate(&msg.astroport_liquidity_manager)?,
     )?;
 
+    if let Some(fee_config) = msg.fee_config {
+        let fee_config = fee_config.check(deps.as_ref())?;
+        FEE_CONFIG.save(deps.storage, &fee_config)?;
+    }
+
     Ok(Response::default())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
@@ -67,8 +88,15 @@ pub fn execute(
             vault_address,
             recipient,
             min_out,
+            max_slippage,
+            referral,
+            leg_min_outs,
         } => {
             let assets = assets.check(deps.api)?;
+            let leg_min_outs = leg_min_outs
+                .map(|x| x.check(api))
+                .transpose()?
+                .unwrap_or_default();
             execute_deposit(
                 deps,
                 env,
@@ -77,15 +105,32 @@ pub fn execute(
                 api.addr_validate(&vault_address)?,
                 recipient,
                 min_out,
+                max_slippage,
+                referral,
+                leg_min_outs,
             )
         }
+        ExecuteMsg::BatchDeposit {
+            deposits,
+            assets,
+            recipient,
+        } => {
+            let assets = assets.check(deps.api)?;
+            execute_batch_deposit(deps, env, info, deposits, assets, recipient)
+        }
         ExecuteMsg::Redeem {
             vault_address,
             recipient,
             receive_choice,
             min_out,
+            referral,
+            leg_min_outs,
         } => {
             let min_out = min_out.check(deps.api)?;
+            let leg_min_outs = leg_min_outs
+                .map(|x| x.check(api))
+                .transpose()?
+                .unwrap_or_default();
             execute_redeem(
                 deps,
                 env,
@@ -94,6 +139,29 @@ pub fn execute(
                 recipient,
                 receive_choice,
                 min_out,
+                referral,
+                leg_min_outs,
+            )
+        }
+        ExecuteMsg::BatchRedeem {
+            redemptions,
+            recipient,
+            receive_choice,
+            min_out,
+        } => {
+            let redemptions = redemptions
+                .iter()
+                .map(|x| api.addr_validate(x))
+                .collect::<StdResult<Vec<_>>>()?;
+            let min_out = min_out.check(deps.api)?;
+            execute_batch_redeem(
+                deps,
+                env,
+                info,
+                redemptions,
+                recipient,
+                receive_choice,
+                min_out,
             )
         }
         ExecuteMsg::ZapBaseTokens {
@@ -101,9 +169,15 @@ pub fn execute(
             recipient,
             receive_choice,
             min_out,
+            referral,
+            leg_min_outs,
         } => {
             let base_token = base_token.check(deps.api)?;
             let min_out = min_out.check(deps.api)?;
+            let leg_min_outs = leg_min_outs
+                .map(|x| x.check(api))
+                .transpose()?
+                .unwrap_or_default();
             execute_zap_base_tokens(
                 deps,
                 env,
@@ -112,19 +186,38 @@ pub fn execute(
                 recipient,
                 receive_choice,
                 min_out,
+                referral,
+                leg_min_outs,
             )
         }
         ExecuteMsg::Unlock { vault_address } => {
             execute_unlock(deps, env, info, api.addr_validate(&vault_address)?)
         }
+        ExecuteMsg::TransferUnlockingPosition {
+            vault_address,
+            lockup_id,
+            new_owner,
+        } => execute_transfer_unlocking_position(
+            deps,
+            info,
+            api.addr_validate(&vault_address)?,
+            lockup_id,
+            api.addr_validate(&new_owner)?,
+        ),
         ExecuteMsg::WithdrawUnlocked {
             vault_address,
             lockup_id,
             recipient,
             receive_choice,
             min_out,
+            referral,
+            leg_min_outs,
         } => {
             let min_out = min_out.check(deps.api)?;
+            let leg_min_outs = leg_min_outs
+                .map(|x| x.check(api))
+                .transpose()?
+                .unwrap_or_default();
             execute_withdraw_unlocked(
                 deps,
                 env,
@@ -134,8 +227,249 @@ pub fn execute(
                 recipient,
                 receive_choice,
                 min_out,
+                referral,
+                leg_min_outs,
+            )
+        }
+        ExecuteMsg::WithdrawUnlockedMany {
+            positions,
+            recipient,
+            receive_choice,
+            min_out,
+        } => {
+            let positions = positions
+                .iter()
+                .map(|(vault_address, lockup_id)| {
+                    Ok((api.addr_validate(vault_address)?, *lockup_id))
+                })
+                .collect::<StdResult<Vec<_>>>()?;
+            let min_out = min_out.check(deps.api)?;
+            execute_withdraw_unlocked_many(
+                deps,
+                env,
+                info,
+                positions,
+                recipient,
+                receive_choice,
+                min_out,
             )
         }
+        ExecuteMsg::WithdrawAllMatured {
+            vault_address,
+            recipient,
+            receive_choice,
+            min_out,
+            start_after_vault_addr,
+            start_after_id,
+            limit,
+        } => {
+            let vault_address = vault_address.map(|x| api.addr_validate(&x)).transpose()?;
+            let min_out = min_out.check(deps.api)?;
+            execute_withdraw_all_matured(
+                deps,
+                env,
+                info,
+                vault_address,
+                recipient,
+                receive_choice,
+                min_out,
+                start_after_vault_addr,
+                start_after_id,
+                limit,
+            )
+        }
+        ExecuteMsg::WithdrawUnlockedToVault {
+            vault_address,
+            lockup_id,
+            to_vault,
+            recipient,
+            min_out,
+            leg_min_outs,
+        } => {
+            let leg_min_outs = leg_min_outs
+                .map(|x| x.check(api))
+                .transpose()?
+                .unwrap_or_default();
+            execute_withdraw_unlocked_to_vault(
+                deps,
+                env,
+                info,
+                api.addr_validate(&vault_address)?,
+                lockup_id,
+                api.addr_validate(&to_vault)?,
+                recipient,
+                min_out,
+                leg_min_outs,
+            )
+        }
+        ExecuteMsg::MigrateBetweenVaults {
+            from_vault,
+            to_vault,
+            recipient,
+            min_out,
+            leg_min_outs,
+        } => {
+            let leg_min_outs = leg_min_outs
+                .map(|x| x.check(api))
+                .transpose()?
+                .unwrap_or_default();
+            execute_migrate_between_vaults(
+                deps,
+                env,
+                info,
+                api.addr_validate(&from_vault)?,
+                api.addr_validate(&to_vault)?,
+                recipient,
+                min_out,
+                leg_min_outs,
+            )
+        }
+        ExecuteMsg::Receive(cw20_msg) => {
+            // The sending CW20 contract is the asset that was received
+            let asset = Asset::cw20(info.sender, cw20_msg.amount);
+            let sender = api.addr_validate(&cw20_msg.sender)?;
+            match from_json(&cw20_msg.msg)? {
+                Cw20HookMsg::Deposit {
+                    vault_address,
+                    recipient,
+                    min_out,
+                    max_slippage,
+                    referral,
+                    leg_min_outs,
+                } => {
+                    let leg_min_outs = leg_min_outs
+                        .map(|x| x.check(api))
+                        .transpose()?
+                        .unwrap_or_default();
+                    let vault_address = api.addr_validate(&vault_address)?;
+                    let assets: AssetList = vec![asset].into();
+                    let touched_assets =
+                        deposit_touched_assets(deps.as_ref(), &assets, &vault_address)?;
+                    let return_leftovers_msg =
+                        reserve_balances(deps.branch(), &env, touched_assets, &assets, &sender)?;
+                    let res = deposit(
+                        deps,
+                        env,
+                        sender,
+                        assets,
+                        vault_address,
+                        recipient,
+                        min_out,
+                        max_slippage,
+                        referral,
+                        leg_min_outs,
+                    )?;
+                    Ok(res.add_message(return_leftovers_msg))
+                }
+                Cw20HookMsg::Redeem {
+                    vault_address,
+                    recipient,
+                    receive_choice,
+                    min_out,
+                    referral,
+                    leg_min_outs,
+                } => {
+                    let min_out = min_out.check(api)?;
+                    let leg_min_outs = leg_min_outs
+                        .map(|x| x.check(api))
+                        .transpose()?
+                        .unwrap_or_default();
+                    let vault_address = api.addr_validate(&vault_address)?;
+                    let touched_assets = withdraw_touched_assets(
+                        deps.as_ref(),
+                        std::slice::from_ref(&vault_address),
+                    )?;
+                    let return_leftovers_msg = reserve_balances(
+                        deps.branch(),
+                        &env,
+                        touched_assets,
+                        &AssetList::new(),
+                        &sender,
+                    )?;
+                    let res = redeem(
+                        deps,
+                        env,
+                        sender,
+                        vault_address,
+                        asset,
+                        recipient,
+                        receive_choice,
+                        Some(min_out),
+                        referral,
+                        leg_min_outs,
+                    )?;
+                    Ok(res.add_message(return_leftovers_msg))
+                }
+                Cw20HookMsg::Unlock { vault_address } => {
+                    unlock(deps, env, sender, api.addr_validate(&vault_address)?, asset)
+                }
+                Cw20HookMsg::ZapBaseTokens {
+                    recipient,
+                    receive_choice,
+                    min_out,
+                    referral,
+                    leg_min_outs,
+                } => {
+                    let min_out = min_out.check(api)?;
+                    let leg_min_outs = leg_min_outs
+                        .map(|x| x.check(api))
+                        .transpose()?
+                        .unwrap_or_default();
+                    let touched_assets = base_token_assets(deps.as_ref(), &asset.info)?;
+                    let return_leftovers_msg = reserve_balances(
+                        deps.branch(),
+                        &env,
+                        touched_assets,
+                        &vec![asset.clone()].into(),
+                        &sender,
+                    )?;
+                    let res = zap_base_tokens(
+                        deps,
+                        env,
+                        sender,
+                        asset,
+                        recipient,
+                        receive_choice,
+                        min_out,
+                        referral,
+                        leg_min_outs,
+                    )?;
+                    Ok(res.add_message(return_leftovers_msg))
+                }
+                Cw20HookMsg::MigrateBetweenVaults {
+                    from_vault,
+                    to_vault,
+                    recipient,
+                    min_out,
+                    leg_min_outs,
+                } => {
+                    let leg_min_outs = leg_min_outs
+                        .map(|x| x.check(api))
+                        .transpose()?
+                        .unwrap_or_default();
+                    migrate_between_vaults(
+                        deps,
+                        env,
+                        sender,
+                        api.addr_validate(&from_vault)?,
+                        asset,
+                        api.addr_validate(&to_vault)?,
+                        recipient,
+                        min_out,
+                        leg_min_outs,
+                    )
+                }
+            }
+        }
+        ExecuteMsg::UpdateConfig { updates } => execute_update_config(deps, info, updates),
+        ExecuteMsg::Sweep { assets, recipient } => {
+            let assets = assets.check(api)?;
+            execute_sweep(deps, info, assets, recipient)
+        }
+        ExecuteMsg::UpdateOwnership(action) => {
+            let ownership = cw_ownable::update_ownership(deps, &env.block, &info.sender, action)?;
+            Ok(Response::new().add_attributes(ownership.into_attributes()))
+        }
         ExecuteMsg::Callback(msg) => {
             // Can only be called by self
             if info.sender != env.contract.address {
@@ -148,6 +482,7 @@ pub fn execute(
                     recipient,
                     pool,
                     deposit_asset_info,
+                    min_out,
                 } => callback_provide_liquidity(
                     deps,
                     env,
@@ -156,7 +491,11 @@ pub fn execute(
                     recipient,
                     pool,
                     deposit_asset_info,
+                    min_out,
                 ),
+                CallbackMsg::ProvideInnerLiquidity { pool } => {
+                    callback_provide_inner_liquidity(deps, env, pool)
+                }
                 CallbackMsg::Deposit {
                     vault_address,
                     recipient,
@@ -174,12 +513,22 @@ pub fn execute(
                     recipient,
                     balances_before,
                     min_out,
-                } => callback_enforce_min_out(deps, assets, recipient, balances_before, min_out),
+                    offer_assets,
+                } => callback_enforce_min_out(
+                    deps,
+                    assets,
+                    recipient,
+                    balances_before,
+                    min_out,
+                    offer_assets,
+                ),
                 CallbackMsg::AfterRedeem {
                     receive_choice,
                     vault_base_token,
                     recipient,
                     min_out,
+                    fees,
+                    leg_min_outs,
                 } => callback_after_redeem(
                     deps,
                     env,
@@ -187,12 +536,65 @@ pub fn execute(
                     vault_base_token,
                     recipient,
                     min_out,
+                    fees,
+                    leg_min_outs,
                 ),
                 CallbackMsg::AfterWithdrawLiq {
                     assets,
                     receive_choice,
                     recipient,
-                } => callback_after_withdraw_liq(deps, env, assets, receive_choice, recipient),
+                    fees,
+                    inner_pools,
+                    swap_min_outs,
+                } => callback_after_withdraw_liq(
+                    deps,
+                    env,
+                    assets,
+                    receive_choice,
+                    recipient,
+                    fees,
+                    inner_pools,
+                    swap_min_outs,
+                ),
+                CallbackMsg::ZapIntoVault {
+                    base_token,
+                    vault_address,
+                    recipient,
+                    min_out,
+                    leg_min_outs,
+                } => callback_zap_into_vault(
+                    deps,
+                    env,
+                    base_token,
+                    vault_address,
+                    recipient,
+                    min_out,
+                    leg_min_outs,
+                ),
+                CallbackMsg::DepositBalances {
+                    assets,
+                    vault_address,
+                    recipient,
+                    min_out,
+                    inner_pools,
+                    leg_min_outs,
+                } => callback_deposit_balances(
+                    deps,
+                    env,
+                    assets,
+                    vault_address,
+                    recipient,
+                    min_out,
+                    inner_pools,
+                    leg_min_outs,
+                ),
+                CallbackMsg::BatchDeposit {
+                    deposits,
+                    recipient,
+                } => callback_batch_deposit(deps, env, deposits, recipient),
+                CallbackMsg::ReturnLeftovers { assets, recipient } => {
+                    callback_return_leftovers(deps, env, assets, recipient)
+                }
             }
         }
     }
@@ -235,6 +637,56 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             start_after_id,
             limit,
         )?),
+        QueryMsg::AnnotatedUserUnlockingPositions {
+            owner,
+            start_after_vault_addr,
+            start_after_id,
+            limit,
+            claimable_only,
+        } => to_json_binary(&query_annotated_user_unlocking_positions(
+            deps,
+            env,
+            deps.api.addr_validate(&owner)?,
+            start_after_vault_addr,
+            start_after_id,
+            limit,
+            claimable_only.unwrap_or(false),
+        )?),
+        QueryMsg::SimulateDeposit {
+            assets,
+            vault_address,
+            referral,
+        } => {
+            let vault_address = deps.api.addr_validate(&vault_address)?;
+            let fees = Fees::resolve(deps, Some(&vault_address), referral.as_ref())
+                .map_err(|e| StdError::generic_err(e.to_string()))?;
+            to_json_binary(&query_simulate_deposit(
+                deps,
+                env,
+                assets.check(deps.api)?,
+                vault_address,
+                &fees,
+            )?)
+        }
+        QueryMsg::SimulateRedeem {
+            vault_address,
+            vault_token_amount,
+            receive_choice,
+            referral,
+        } => to_json_binary(&query_simulate_redeem(
+            deps,
+            deps.api.addr_validate(&vault_address)?,
+            vault_token_amount,
+            receive_choice,
+            referral,
+        )?),
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::FeeConfig {} => to_json_binary(&FEE_CONFIG.may_load(deps.storage)?),
+        QueryMsg::OracleConfig {} => to_json_binary(&ORACLE_CONFIG.may_load(deps.storage)?),
+        QueryMsg::ContractBalances { asset_infos } => {
+            to_json_binary(&query_contract_balances(deps, env, asset_infos)?)
+        }
+        QueryMsg::Ownership {} => to_json_binary(&cw_ownable::get_ownership(deps.storage)?),
     }
 }
 
@@ -271,6 +723,237 @@ pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractE
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    Ok(Response::default())
+pub fn migrate(mut deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Verify the stored contract name, refuse downgrades and bump the stored
+    // version
+    let old_version = ensure_from_older_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // Contracts from before owners were introduced need to be given one
+    let has_owner = cw_ownable::get_ownership(deps.storage).is_ok_and(|o| o.owner.is_some());
+    if let Some(owner) = &msg.owner {
+        if has_owner {
+            return Err(ContractError::Generic(
+                "Contract already has an owner. Use UpdateOwnership instead".to_string(),
+            ));
+        }
+        cw_ownable::initialize_owner(deps.storage, deps.api, Some(owner))?;
+    } else if !has_owner {
+        return Err(ContractError::Generic(
+            "Contract has no owner, one must be set in the migrate msg".to_string(),
+        ));
+    }
+
+    let mut res = Response::new().add_event(
+        Event::new("apollo/vault-zapper/migrate")
+            .add_attribute("from_version", old_version.to_string())
+            .add_attribute("to_version", CONTRACT_VERSION),
+    );
+
+    if let Some(config_updates) = msg.config_updates {
+        res = res.add_event(apply_config_updates(deps.branch(), config_updates)?);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use apollo_cw_asset::AssetUnchecked;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{
+        coin, Addr, BankMsg, ContractResult, CosmosMsg, Empty, SystemResult, Uint128, WasmMsg,
+        WasmQuery,
+    };
+    use cw20::{BalanceResponse, Cw20QueryMsg, Cw20ReceiveMsg};
+    use cw_dex_router::helpers::{CwDexRouter, CwDexRouterUnchecked};
+    use cw_vault_standard::{VaultInfoResponse, VaultStandardExecuteMsg};
+
+    use crate::msg::{ConfigUpdates, Cw20HookMsg, ReceiveChoice};
+    use crate::state::RESERVED_BALANCES;
+
+    #[test]
+    fn migrate_sets_owner_and_bumps_version() {
+        let mut deps = mock_dependencies();
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.2.0").unwrap();
+
+        // Owner must be set when migrating from a version without one
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+
+        let msg = MigrateMsg {
+            owner: Some("owner".to_string()),
+            config_updates: Some(ConfigUpdates {
+                router: Some(CwDexRouterUnchecked::new("router".to_string())),
+                ..Default::default()
+            }),
+        };
+        migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+
+        let version = cw2::get_contract_version(deps.as_ref().storage).unwrap();
+        assert_eq!(version.version, CONTRACT_VERSION);
+        assert!(cw_ownable::is_owner(deps.as_ref().storage, &Addr::unchecked("owner")).unwrap());
+        assert_eq!(
+            ROUTER.load(deps.as_ref().storage).unwrap(),
+            CwDexRouter::new(&Addr::unchecked("router"))
+        );
+
+        // Owner can not be overwritten by a migration
+        migrate(deps.as_mut(), mock_env(), msg).unwrap_err();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+    }
+
+    #[test]
+    fn migrate_refuses_downgrade_and_other_contracts() {
+        let mut deps = mock_dependencies();
+        let api = deps.api;
+        cw_ownable::initialize_owner(deps.as_mut().storage, &api, Some("owner")).unwrap();
+
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "999.0.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+
+        set_contract_version(deps.as_mut().storage, "crates.io:other-contract", "0.1.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+    }
+
+    #[test]
+    fn sweep_is_refused_while_an_operation_is_in_progress() {
+        let mut deps = mock_dependencies();
+        let api = deps.api;
+        cw_ownable::initialize_owner(deps.as_mut().storage, &api, Some("owner")).unwrap();
+        let msg = ExecuteMsg::Sweep {
+            assets: vec![AssetUnchecked::native("uaxl", 100u128)].into(),
+            recipient: None,
+        };
+
+        RESERVED_BALANCES
+            .save(deps.as_mut().storage, &AssetList::new())
+            .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::OperationInProgress {}));
+
+        RESERVED_BALANCES.remove(deps.as_mut().storage);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "owner".to_string(),
+                amount: vec![coin(100, "uaxl")],
+            })
+        );
+    }
+
+    #[test]
+    fn redeem_cw20_vault_token_via_receive_hook() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            // A CW20 vault token is issued by the vault contract itself, unlike
+            // the vault token of "other_vault"
+            WasmQuery::Smart { contract_addr, msg }
+                if (contract_addr == "vault" || contract_addr == "other_vault")
+                    && from_json::<Cw20QueryMsg>(msg).is_err() =>
+            {
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&VaultInfoResponse {
+                        base_token: "base_token".to_string(),
+                        vault_token: "vault".to_string(),
+                    })
+                    .unwrap(),
+                ))
+            }
+            // The zapper holds none of the CW20 base token, which is not an LP
+            // token or vault token
+            WasmQuery::Smart { contract_addr, msg }
+                if contract_addr == "base_token"
+                    && matches!(from_json(msg), Ok(Cw20QueryMsg::Balance { .. })) =>
+            {
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&BalanceResponse {
+                        balance: Uint128::zero(),
+                    })
+                    .unwrap(),
+                ))
+            }
+            _ => SystemResult::Ok(ContractResult::Err("Unexpected query".to_string())),
+        });
+        #[cfg(feature = "astroport")]
+        ASTROPORT_LIQUIDITY_MANAGER
+            .save(deps.as_mut().storage, &Addr::unchecked("liquidity_manager"))
+            .unwrap();
+
+        let receive_msg = Cw20ReceiveMsg {
+            sender: "user".to_string(),
+            amount: Uint128::new(100),
+            msg: to_json_binary(&Cw20HookMsg::Redeem {
+                vault_address: "vault".to_string(),
+                recipient: None,
+                receive_choice: ReceiveChoice::BaseToken,
+                min_out: vec![].into(),
+                referral: None,
+                leg_min_outs: None,
+            })
+            .unwrap(),
+        };
+
+        // CW20 tokens other than the vault token are rejected
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("other_token", &[]),
+            ExecuteMsg::Receive(receive_msg.clone()),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidVaultToken {}));
+        // A failed transaction reverts the reservation of its balances
+        RESERVED_BALANCES.remove(deps.as_mut().storage);
+
+        // CW20 vault tokens not issued by the vault itself are rejected, as they
+        // are redeemed without a transfer or allowance
+        let mut other_vault_msg = receive_msg.clone();
+        other_vault_msg.msg = to_json_binary(&Cw20HookMsg::Redeem {
+            vault_address: "other_vault".to_string(),
+            recipient: None,
+            receive_choice: ReceiveChoice::BaseToken,
+            min_out: vec![].into(),
+            referral: None,
+            leg_min_outs: None,
+        })
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("vault", &[]),
+            ExecuteMsg::Receive(other_vault_msg),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidVaultToken {}));
+        RESERVED_BALANCES.remove(deps.as_mut().storage);
+
+        // The vault tokens are redeemed without sending any funds
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("vault", &[]),
+            ExecuteMsg::Receive(receive_msg),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "vault".to_string(),
+                msg: to_json_binary(&VaultStandardExecuteMsg::<Empty>::Redeem {
+                    recipient: None,
+                    amount: Uint128::new(100),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
 }
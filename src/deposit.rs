@@ -1,44 +1,234 @@
 use apollo_cw_asset::{Asset, AssetInfo, AssetList};
 use apollo_utils::assets::receive_assets;
+use apollo_utils::responses::merge_responses;
 use cosmwasm_std::{
-    to_json_binary, Addr, Binary, Coin, DepsMut, Empty, Env, Event, MessageInfo, Response, Uint128,
+    to_json_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env, Event,
+    MessageInfo, Response, Uint128,
 };
 use cw_vault_standard::VaultContract;
 
-use crate::helpers::VaultHelper;
-use crate::msg::{CallbackMsg, Pool};
-use crate::state::{ASTROPORT_LIQUIDITY_MANAGER, LIQUIDITY_HELPER, ROUTER};
+use crate::fees::{Fees, Referral};
+use crate::helpers::{
+    base_token_assets, get_nested_pools, get_vault_for_vault_token, query_unreserved_balances,
+    reserve_balances, split_by_weights, VaultHelper,
+};
+use crate::msg::{BatchDepositEntry, CallbackMsg, LegMinOuts, Pool};
+use crate::oracle::{check_oracle_rate, swap_min_receive};
+use crate::query::query_simulate_deposit;
+use crate::state::{ASTROPORT_LIQUIDITY_MANAGER, LIQUIDITY_HELPER, RESERVED_BALANCES, ROUTER};
 use crate::ContractError;
 
 pub fn execute_deposit(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     assets: AssetList,
     vault_address: Addr,
     recipient: Option<String>,
     min_out: Uint128,
+    max_slippage: Option<Decimal>,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
 ) -> Result<Response, ContractError> {
-    // Unwrap recipient or use sender
-    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
-
     let receive_assets_res = receive_assets(&info, &env, &assets)?;
 
-    // Query the vault info to get the deposit asset
-    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
-    let deposit_asset_info = match deps.api.addr_validate(&vault.base_token) {
+    // Reserve the contract's balances of the assets touched by the deposit,
+    // excluding the native tokens sent with this message, so that the deposit
+    // only uses its own assets and any leftovers are refunded to the sender
+    let touched_assets = deposit_touched_assets(deps.as_ref(), &assets, &vault_address)?;
+    let return_leftovers_msg = reserve_balances(
+        deps.branch(),
+        &env,
+        touched_assets,
+        &info.funds.clone().into(),
+        &info.sender,
+    )?;
+
+    let deposit_res = deposit(
+        deps,
+        env,
+        info.sender,
+        assets,
+        vault_address,
+        recipient,
+        min_out,
+        max_slippage,
+        referral,
+        leg_min_outs,
+    )?;
+
+    Ok(merge_responses(vec![receive_assets_res, deposit_res]).add_message(return_leftovers_msg))
+}
+
+/// Returns the assets that a deposit of `assets` into the vault can leave
+/// behind in the contract: the deposited assets, the base token and, if the
+/// base token is an LP token or another vault's token, the assets it is made
+/// of.
+pub fn deposit_touched_assets(
+    deps: Deps,
+    assets: &AssetList,
+    vault_address: &Addr,
+) -> Result<Vec<AssetInfo>, ContractError> {
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, vault_address)?;
+    let base_token = match deps.api.addr_validate(&vault.base_token) {
         Ok(addr) => AssetInfo::cw20(addr),
         Err(_) => AssetInfo::native(&vault.base_token),
     };
 
+    let mut touched_assets: Vec<AssetInfo> = assets.iter().map(|a| a.info.clone()).collect();
+    for info in base_token_assets(deps, &base_token)? {
+        if !touched_assets.contains(&info) {
+            touched_assets.push(info);
+        }
+    }
+
+    Ok(touched_assets)
+}
+
+pub fn execute_batch_deposit(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    deposits: Vec<BatchDepositEntry>,
+    assets: AssetList,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    // Validate the deposits and weights
+    let invalid = |reason: &str| ContractError::InvalidBatch {
+        reason: reason.to_string(),
+    };
+    if deposits.is_empty() {
+        return Err(invalid("no deposits"));
+    }
+    let mut weights: Vec<((Addr, Uint128), Decimal)> = vec![];
+    let mut total_weight = Decimal::zero();
+    for deposit in &deposits {
+        if deposit.weight.is_zero() {
+            return Err(invalid("weights must be positive"));
+        }
+        let vault_address = deps.api.addr_validate(&deposit.vault_address)?;
+        if weights
+            .iter()
+            .any(|((other, _), _)| *other == vault_address)
+        {
+            return Err(invalid("duplicate vault"));
+        }
+        total_weight += deposit.weight;
+        weights.push(((vault_address, deposit.min_out), deposit.weight));
+    }
+    if total_weight != Decimal::one() {
+        return Err(invalid("weights must sum to 1"));
+    }
+
+    let receive_assets_res = receive_assets(&info, &env, &assets)?;
+
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
+
+    // Reserve the contract's balances of the assets touched by any of the
+    // deposits, excluding the native tokens sent with this message, and the
+    // assets for the deposits, which are released one at a time in
+    // callback_batch_deposit so that no deposit uses another one's assets
+    let mut touched_assets: Vec<AssetInfo> = vec![];
+    for ((vault_address, _), _) in &weights {
+        for info in deposit_touched_assets(deps.as_ref(), &assets, vault_address)? {
+            if !touched_assets.contains(&info) {
+                touched_assets.push(info);
+            }
+        }
+    }
+    let return_leftovers_msg = reserve_balances(
+        deps.branch(),
+        &env,
+        touched_assets,
+        &info.funds.clone().into(),
+        &info.sender,
+    )?;
+    let mut reserved = RESERVED_BALANCES.load(deps.storage)?;
+    reserved.add_many(&assets)?;
+    RESERVED_BALANCES.save(deps.storage, &reserved)?;
+
+    let deposits = split_by_weights(&assets, &weights)?
+        .into_iter()
+        .map(|((vault_address, min_out), assets)| (vault_address, assets, min_out))
+        .collect();
+
+    let event = Event::new("apollo/vault-zapper/execute_batch_deposit")
+        .add_attribute("assets", to_json_binary(&assets)?.to_string())
+        .add_attribute("deposits", to_json_binary(&deposits)?.to_string())
+        .add_attribute("recipient", &recipient);
+
+    let res = Response::new()
+        .add_message(
+            CallbackMsg::BatchDeposit {
+                deposits,
+                recipient,
+            }
+            .into_cosmos_msg(&env)?,
+        )
+        .add_message(return_leftovers_msg)
+        .add_event(event);
+
+    Ok(merge_responses(vec![receive_assets_res, res]))
+}
+
+/// Deposits `assets`, which must already be held by the contract, into the
+/// vault. Called by `execute_deposit` and by the CW20 receive hook.
+///
+/// `leg_min_outs` sets minimum amounts for the swaps and the liquidity
+/// provision that the deposit consists of, on top of `min_out`.
+pub fn deposit(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    assets: AssetList,
+    vault_address: Addr,
+    recipient: Option<String>,
+    min_out: Uint128,
+    max_slippage: Option<Decimal>,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(sender), |x| deps.api.addr_validate(&x))?;
+
+    let fees = Fees::resolve(deps.as_ref(), Some(&vault_address), referral.as_ref())?;
+
+    // If a max slippage is given, simulate the deposit to get the minimum amount
+    // of vault tokens to receive
+    let min_out = match max_slippage {
+        Some(max_slippage) => {
+            if max_slippage > Decimal::one() {
+                return Err(ContractError::InvalidMaxSlippage {});
+            }
+            let simulated = query_simulate_deposit(
+                deps.as_ref(),
+                env.clone(),
+                assets.clone(),
+                vault_address.clone(),
+                &fees,
+            )?;
+            min_out.max(simulated.vault_token_amount * (Decimal::one() - max_slippage))
+        }
+        None => min_out,
+    };
+
+    // Take the protocol and referral fees from the deposited assets
+    let (fee_msgs, assets_after_fee, fee_attrs) = fees.take_from_assets(&assets)?;
+    let res = Response::new().add_messages(fee_msgs);
+
+    // Query the vault info to get the vault token
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
+
     // Add a message to enforce the minimum amount of vault tokens received
-    let vault_token = AssetInfo::native(&vault.vault_token);
+    let vault_token = vault.vault_token_info(deps.api);
     let balance_before = vault_token.query_balance(&deps.querier, recipient.clone())?;
     let enforce_min_out_msg = CallbackMsg::EnforceMinOut {
         assets: vec![vault_token.clone()],
         recipient: recipient.clone(),
         balances_before: vec![Asset::new(vault_token.clone(), balance_before)].into(),
         min_out: vec![Asset::new(vault_token.clone(), min_out)].into(),
+        offer_assets: assets_after_fee.clone(),
     }
     .into_cosmos_msg(&env)?;
 
@@ -46,7 +236,45 @@ pub fn execute_deposit(
         .add_attribute("assets", to_json_binary(&assets)?.to_string())
         .add_attribute("vault_address", &vault_address)
         .add_attribute("recipient", &recipient)
-        .add_attribute("min_out", min_out);
+        .add_attribute("min_out", min_out)
+        .add_attribute("leg_min_outs", to_json_binary(&leg_min_outs)?.to_string())
+        .add_attributes(fee_attrs);
+
+    let msgs = zap_into_vault_msgs(
+        deps.as_ref(),
+        &env,
+        assets_after_fee,
+        &vault,
+        recipient,
+        &leg_min_outs,
+    )?;
+
+    Ok(res
+        .add_messages(msgs)
+        .add_message(enforce_min_out_msg)
+        .add_event(event))
+}
+
+/// Returns messages that convert `assets`, which must already be held by the
+/// contract, into the vault's base token and deposit it into the vault with
+/// `recipient` as the recipient of the vault tokens.
+///
+/// If the base token is the vault token of another vault, the assets are first
+/// zapped into that inner vault and the received inner vault tokens are then
+/// deposited.
+fn zap_into_vault_msgs(
+    deps: Deps,
+    env: &Env,
+    assets: AssetList,
+    vault: &VaultContract<Empty, Empty>,
+    recipient: Addr,
+    leg_min_outs: &LegMinOuts,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let vault_address = vault.addr.clone();
+    let deposit_asset_info = match deps.api.addr_validate(&vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&vault.base_token),
+    };
 
     // Check if coins sent are already same as the depositable assets
     // If yes, then just deposit the coins
@@ -58,69 +286,106 @@ pub fn execute_deposit(
             Some(recipient.to_string()),
         )?;
 
-        return Ok(receive_assets_res
-            .add_messages(msgs)
-            .add_message(enforce_min_out_msg)
-            .add_event(event));
+        return Ok(msgs);
     }
 
-    //Check if the depositable asset is an LP token
+    // Check if the depositable asset is an LP token, possibly of a pool that
+    // contains other LP tokens
     let astroport_liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?;
-    let pool = Pool::get_pool_for_lp_token(
-        deps.as_ref(),
-        &deposit_asset_info,
-        astroport_liquidity_manager,
-    )
-    .ok();
+    let pools = get_nested_pools(deps, &deposit_asset_info, astroport_liquidity_manager)?;
+
+    // If the depositable asset is the vault token of another vault, zap into
+    // that vault first and then deposit all of the received vault tokens
+    if pools.is_empty() {
+        if let Some(inner_vault) = get_vault_for_vault_token(deps, &deposit_asset_info) {
+            if inner_vault.addr != vault_address {
+                let mut msgs = zap_into_vault_msgs(
+                    deps,
+                    env,
+                    assets,
+                    &inner_vault,
+                    env.contract.address.clone(),
+                    leg_min_outs,
+                )?;
+                msgs.push(
+                    CallbackMsg::Deposit {
+                        vault_address,
+                        recipient,
+                        deposit_asset_info,
+                    }
+                    .into_cosmos_msg(env)?,
+                );
+                return Ok(msgs);
+            }
+        }
+    }
 
     // Set the target of the basket liquidation, depending on if depositable asset
     // is an LP token or not
-    let receive_asset_infos = match &pool {
-        Some(pool) => {
-            // Get the assets in the pool
-            pool.get_pool_liquidity(deps.as_ref())?
-                .into_iter()
-                .map(|x| x.info.clone())
-                .collect()
+    let (receive_asset_infos, receive_asset_info) = match pools.last() {
+        Some(innermost_pool) => {
+            // Assets of any of the pools are provided as liquidity as is. The
+            // rest is liquidated into the first asset of the innermost pool.
+            let mut receive_asset_infos: Vec<AssetInfo> = vec![];
+            for pool in &pools {
+                for info in pool.pool_assets(deps)? {
+                    if !receive_asset_infos.contains(&info) {
+                        receive_asset_infos.push(info);
+                    }
+                }
+            }
+            let receive_asset_info = innermost_pool.pool_assets(deps)?[0].clone();
+            (receive_asset_infos, receive_asset_info)
         }
         None => {
             //Not an LP token. Use the depositable_asset as the target for the basket
             // liquidation
-            vec![deposit_asset_info.clone()]
+            (vec![deposit_asset_info.clone()], deposit_asset_info.clone())
         }
     };
 
-    // Basket Liquidate deposited coins
-    // We only liquidate the coins that are not already the target asset
-    let liquidate_coins = assets
+    // Basket Liquidate deposited assets
+    // We only liquidate the assets that are not already the target asset
+    let liquidate_assets: AssetList = assets
         .into_iter()
-        .filter_map(|a| {
-            if !receive_asset_infos.contains(&a.info) {
-                a.try_into().ok()
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<Coin>>();
-    let receive_asset_info = receive_asset_infos[0].clone();
-    let mut msgs = if !liquidate_coins.is_empty() {
+        .filter(|a| !receive_asset_infos.contains(&a.info))
+        .cloned()
+        .collect::<Vec<_>>()
+        .into();
+    let mut msgs = if liquidate_assets.len() > 0 {
         let router = ROUTER.load(deps.storage)?;
-        router.basket_liquidate_msgs(liquidate_coins.into(), &receive_asset_info, None, None)?
+        let min_receive = swap_min_receive(
+            deps,
+            &liquidate_assets,
+            &receive_asset_info,
+            leg_min_outs.swap_min_out(&receive_asset_info),
+        )?;
+        router.basket_liquidate_msgs(liquidate_assets, &receive_asset_info, min_receive, None)?
     } else {
         vec![]
     };
 
-    // If the depositable asset is an LP token, we add a message to provide
-    // liquidity for this pool
-    if let Some(pool) = pool {
+    // If the depositable asset is an LP token, we add messages to provide
+    // liquidity to the inner pools from the innermost outwards, and then to the
+    // pool of the depositable asset itself
+    if let Some((pool, inner_pools)) = pools.split_first() {
+        for inner_pool in inner_pools.iter().rev() {
+            msgs.push(
+                CallbackMsg::ProvideInnerLiquidity {
+                    pool: inner_pool.clone(),
+                }
+                .into_cosmos_msg(env)?,
+            );
+        }
         msgs.push(
             CallbackMsg::ProvideLiquidity {
                 vault_address,
                 recipient,
-                pool,
+                pool: pool.clone(),
                 deposit_asset_info,
+                min_out: leg_min_outs.provide_liquidity,
             }
-            .into_cosmos_msg(&env)?,
+            .into_cosmos_msg(env)?,
         )
     } else {
         // If the depositable asset is not an LP token, we add a message to deposit the
@@ -131,30 +396,23 @@ pub fn execute_deposit(
                 recipient,
                 deposit_asset_info,
             }
-            .into_cosmos_msg(&env)?,
+            .into_cosmos_msg(env)?,
         );
     }
 
-    Ok(receive_assets_res
-        .add_messages(msgs)
-        .add_message(enforce_min_out_msg)
-        .add_event(event))
+    Ok(msgs)
 }
 
-pub fn callback_provide_liquidity(
-    deps: DepsMut,
-    env: Env,
-    _info: MessageInfo,
-    vault_address: Addr,
-    recipient: Addr,
+/// Returns messages that provide the contract's balances of the pool's assets
+/// as liquidity via the liquidity helper, receiving at least `min_out` LP
+/// tokens.
+fn provide_liquidity_msgs(
+    deps: Deps,
+    env: &Env,
     pool: Pool,
-    deposit_asset_info: AssetInfo,
-) -> Result<Response, ContractError> {
-    let pool_asset_balances = AssetList::query_asset_info_balances(
-        pool.pool_assets(deps.as_ref())?,
-        &deps.querier,
-        &env.contract.address,
-    )?;
+    min_out: Uint128,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let pool_asset_balances = query_unreserved_balances(deps, env, pool.pool_assets(deps)?)?;
 
     let liquidity_helper = LIQUIDITY_HELPER.load(deps.storage)?;
 
@@ -167,12 +425,20 @@ pub fn callback_provide_liquidity(
         _ => panic!("Unsupported pool type"),
     };
 
-    let provide_liquidity_msgs = liquidity_helper.balancing_provide_liquidity(
-        pool_asset_balances,
-        Uint128::zero(),
-        pool,
-        None,
-    )?;
+    Ok(liquidity_helper.balancing_provide_liquidity(pool_asset_balances, min_out, pool, None)?)
+}
+
+pub fn callback_provide_liquidity(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    vault_address: Addr,
+    recipient: Addr,
+    pool: Pool,
+    deposit_asset_info: AssetInfo,
+    min_out: Uint128,
+) -> Result<Response, ContractError> {
+    let provide_liquidity_msgs = provide_liquidity_msgs(deps.as_ref(), &env, pool, min_out)?;
 
     let response = Response::new()
         .add_messages(provide_liquidity_msgs)
@@ -188,6 +454,26 @@ pub fn callback_provide_liquidity(
     Ok(response)
 }
 
+/// Provides the contract's unreserved balances of the assets of an inner pool
+/// as liquidity. Nothing is provided if there are no such balances, e.g. when
+/// only the other assets of the outer pool were deposited.
+pub fn callback_provide_inner_liquidity(
+    deps: DepsMut,
+    env: Env,
+    pool: Pool,
+) -> Result<Response, ContractError> {
+    let pool_asset_balances =
+        query_unreserved_balances(deps.as_ref(), &env, pool.pool_assets(deps.as_ref())?)?;
+    if pool_asset_balances.iter().all(|a| a.amount.is_zero()) {
+        return Ok(Response::new());
+    }
+
+    let provide_liquidity_msgs =
+        provide_liquidity_msgs(deps.as_ref(), &env, pool, Uint128::zero())?;
+
+    Ok(Response::new().add_messages(provide_liquidity_msgs))
+}
+
 pub fn callback_deposit(
     deps: DepsMut,
     env: Env,
@@ -197,7 +483,10 @@ pub fn callback_deposit(
     deposit_asset_info: AssetInfo,
 ) -> Result<Response, ContractError> {
     let amount_to_deposit =
-        deposit_asset_info.query_balance(&deps.querier, env.contract.address)?;
+        query_unreserved_balances(deps.as_ref(), &env, vec![deposit_asset_info.clone()])?
+            .find(&deposit_asset_info)
+            .map(|a| a.amount)
+            .unwrap_or_default();
 
     let vault: VaultContract<_, _> =
         VaultContract::<Empty, Empty>::new(&deps.querier, &vault_address)?;
@@ -210,12 +499,84 @@ pub fn callback_deposit(
     Ok(Response::new().add_messages(msgs))
 }
 
+/// Releases the assets of the first of the remaining deposits of a
+/// `BatchDeposit` and deposits them. The rest are deposited in another
+/// `BatchDeposit` callback after this deposit has completed.
+pub fn callback_batch_deposit(
+    mut deps: DepsMut,
+    env: Env,
+    deposits: Vec<(Addr, AssetList, Uint128)>,
+    recipient: Addr,
+) -> Result<Response, ContractError> {
+    let mut deposits = deposits.into_iter();
+    let (vault_address, assets, min_out) = match deposits.next() {
+        Some(deposit) => deposit,
+        None => return Ok(Response::new()),
+    };
+    let remaining: Vec<_> = deposits.collect();
+
+    let mut reserved = RESERVED_BALANCES.load(deps.storage)?;
+    reserved.deduct_many(&assets)?;
+    RESERVED_BALANCES.save(deps.storage, &reserved)?;
+
+    let res = deposit(
+        deps.branch(),
+        env.clone(),
+        recipient.clone(),
+        assets,
+        vault_address,
+        Some(recipient.to_string()),
+        min_out,
+        None,
+        None,
+        LegMinOuts::default(),
+    )?;
+
+    if remaining.is_empty() {
+        Ok(res)
+    } else {
+        Ok(res.add_message(
+            CallbackMsg::BatchDeposit {
+                deposits: remaining,
+                recipient,
+            }
+            .into_cosmos_msg(&env)?,
+        ))
+    }
+}
+
+/// Sends the contract's unreserved balances of `assets` to `recipient` and
+/// releases the reserved balances, ending the operation in progress.
+pub fn callback_return_leftovers(
+    deps: DepsMut,
+    env: Env,
+    assets: Vec<AssetInfo>,
+    recipient: Addr,
+) -> Result<Response, ContractError> {
+    let leftovers: AssetList = query_unreserved_balances(deps.as_ref(), &env, assets)?
+        .into_iter()
+        .filter(|asset| !asset.amount.is_zero())
+        .cloned()
+        .collect::<Vec<_>>()
+        .into();
+    RESERVED_BALANCES.remove(deps.storage);
+
+    let event = Event::new("apollo/vault-zapper/callback_return_leftovers")
+        .add_attribute("recipient", &recipient)
+        .add_attribute("leftovers", to_json_binary(&leftovers)?.to_string());
+
+    Ok(Response::new()
+        .add_messages(leftovers.transfer_msgs(&recipient)?)
+        .add_event(event))
+}
+
 pub fn callback_enforce_min_out(
     deps: DepsMut,
     assets: Vec<AssetInfo>,
     recipient: Addr,
     balances_before: AssetList,
     min_out: AssetList,
+    offer_assets: AssetList,
 ) -> Result<Response, ContractError> {
     let mut new_balances =
         AssetList::query_asset_info_balances(assets.clone(), &deps.querier, &recipient)?;
@@ -234,6 +595,9 @@ pub fn callback_enforce_min_out(
         }
     }
 
+    // Check the value received against the value given at oracle prices
+    check_oracle_rate(deps.as_ref(), &offer_assets, assets_received)?;
+
     let event = Event::new("apollo/vault-zapper/callback_enforce_min_out")
         .add_attribute("recipient", recipient)
         .add_attribute("assets", to_json_binary(&assets)?.to_string())
@@ -245,3 +609,49 @@ pub fn callback_enforce_min_out(
 
     Ok(Response::new().add_event(event))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env};
+    use cosmwasm_std::{coin, BankMsg};
+
+    #[test]
+    fn return_leftovers_refunds_unreserved_balances() {
+        let mut deps = mock_dependencies_with_balance(&[coin(1500, "uaxl"), coin(1000, "untrn")]);
+        let reserved: AssetList = vec![
+            Asset::native("uaxl", 1000u128),
+            Asset::native("untrn", 1200u128),
+        ]
+        .into();
+        RESERVED_BALANCES
+            .save(deps.as_mut().storage, &reserved)
+            .unwrap();
+
+        // Only the uaxl balance in excess of the reserved balance is refunded.
+        // Balances below their reservation or not held are left as is.
+        let res = callback_return_leftovers(
+            deps.as_mut(),
+            mock_env(),
+            vec![
+                AssetInfo::native("uaxl"),
+                AssetInfo::native("untrn"),
+                AssetInfo::native("uastro"),
+            ],
+            Addr::unchecked("user"),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages.into_iter().map(|x| x.msg).collect::<Vec<_>>(),
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: "user".to_string(),
+                amount: vec![coin(500, "uaxl")],
+            })]
+        );
+        assert!(RESERVED_BALANCES
+            .may_load(deps.as_ref().storage)
+            .unwrap()
+            .is_none());
+    }
+}
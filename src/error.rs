@@ -1,5 +1,6 @@
-use cosmwasm_std::{OverflowError, StdError, Uint128};
+use cosmwasm_std::{Decimal256, OverflowError, StdError, Uint128};
 use cw_dex::CwDexError;
+use cw_ownable::OwnershipError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -16,6 +17,9 @@ pub enum ContractError {
     #[error("{0}")]
     CwDexError(#[from] CwDexError),
 
+    #[error("{0}")]
+    Ownership(#[from] OwnershipError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -33,4 +37,25 @@ pub enum ContractError {
 
     #[error("Invalid min_out argument")]
     InvalidMinOut {},
+
+    #[error("Max slippage must be at most 100%")]
+    InvalidMaxSlippage {},
+
+    #[error("Invalid basket: {reason}")]
+    InvalidBasket { reason: String },
+
+    #[error("Another operation is in progress")]
+    OperationInProgress {},
+
+    #[error("Invalid batch: {reason}")]
+    InvalidBatch { reason: String },
+
+    #[error("Referral fee of {fee_bps} bps exceeds the maximum of {max_fee_bps} bps")]
+    ReferralFeeTooHigh { fee_bps: u16, max_fee_bps: u16 },
+
+    #[error("Value received deviates too far from the oracle price. Expected at least {min_value}, got {received_value}")]
+    OracleRateDeviation {
+        min_value: Decimal256,
+        received_value: Decimal256,
+    },
 }
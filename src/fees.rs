@@ -0,0 +1,199 @@
+use apollo_cw_asset::{Asset, AssetList};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_json_binary, Addr, Attribute, CosmosMsg, Decimal, Deps, StdError, StdResult,
+};
+use cw_address_like::AddressLike;
+
+use crate::state::{FEE_CONFIG, MAX_REFERRAL_FEE_BPS};
+use crate::ContractError;
+
+/// The denominator of fees given in basis points
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// The protocol fee configuration of the zapper.
+#[cw_serde]
+pub struct FeeConfig<T: AddressLike> {
+    /// The fraction of the zapped assets that is taken as a fee, unless
+    /// overridden for the vault in `vault_fee_rates`.
+    pub fee_rate: Decimal,
+    /// The address that receives the fees.
+    pub fee_recipient: T,
+    /// Per-vault overrides of `fee_rate`.
+    pub vault_fee_rates: Vec<(T, Decimal)>,
+}
+
+impl FeeConfig<String> {
+    /// Validates the fee config and returns a `FeeConfig<Addr>`.
+    pub fn check(&self, deps: Deps) -> StdResult<FeeConfig<Addr>> {
+        let rates = [self.fee_rate]
+            .into_iter()
+            .chain(self.vault_fee_rates.iter().map(|(_, rate)| *rate));
+        for rate in rates {
+            if rate >= Decimal::one() {
+                return Err(StdError::generic_err("Fee rate must be less than 100%"));
+            }
+        }
+
+        Ok(FeeConfig {
+            fee_rate: self.fee_rate,
+            fee_recipient: deps.api.addr_validate(&self.fee_recipient)?,
+            vault_fee_rates: self
+                .vault_fee_rates
+                .iter()
+                .map(|(vault, rate)| Ok((deps.api.addr_validate(vault)?, *rate)))
+                .collect::<StdResult<Vec<_>>>()?,
+        })
+    }
+}
+
+impl FeeConfig<Addr> {
+    /// Returns the fee to take when zapping into or out of `vault`. Returns
+    /// `None` if the fee rate is zero.
+    pub fn fee_for_vault(&self, vault: Option<&Addr>) -> Option<Fee> {
+        let fee_rate = vault
+            .and_then(|vault| {
+                self.vault_fee_rates
+                    .iter()
+                    .find(|(addr, _)| addr == vault)
+                    .map(|(_, rate)| *rate)
+            })
+            .unwrap_or(self.fee_rate);
+
+        (!fee_rate.is_zero()).then(|| Fee {
+            rate: fee_rate,
+            recipient: self.fee_recipient.clone(),
+        })
+    }
+}
+
+/// A fee taken from the assets of a single zap.
+#[cw_serde]
+pub struct Fee {
+    /// The fraction of the assets that is taken as a fee
+    pub rate: Decimal,
+    /// The address that receives the fee
+    pub recipient: Addr,
+}
+
+impl Fee {
+    /// Calculates the fee on `assets`. Returns the messages to send the fee to
+    /// the recipient, the fee amounts and the assets remaining after the fee.
+    pub fn take_from_assets(
+        &self,
+        assets: &AssetList,
+    ) -> StdResult<(Vec<CosmosMsg>, AssetList, AssetList)> {
+        let fees: AssetList = assets
+            .iter()
+            .map(|asset| Asset::new(asset.info.clone(), asset.amount * self.rate))
+            .filter(|asset| !asset.amount.is_zero())
+            .collect::<Vec<_>>()
+            .into();
+
+        let mut assets_after_fee = assets.clone();
+        assets_after_fee.deduct_many(&fees)?;
+
+        Ok((fees.transfer_msgs(&self.recipient)?, fees, assets_after_fee))
+    }
+}
+
+/// A referral given by the frontend or integrator that initiated a zap. The
+/// referrer receives `fee_bps` basis points of the zapped assets.
+#[cw_serde]
+pub struct Referral {
+    /// The address that receives the referral fee
+    pub address: String,
+    /// The referral fee in basis points. Must not exceed the maximum set by the
+    /// owner.
+    pub fee_bps: u16,
+}
+
+impl Referral {
+    /// Validates the referral against the configured maximum referral fee and
+    /// returns the corresponding `Fee`.
+    pub fn check(&self, deps: Deps) -> Result<Fee, ContractError> {
+        let max_fee_bps = MAX_REFERRAL_FEE_BPS
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        if self.fee_bps > max_fee_bps {
+            return Err(ContractError::ReferralFeeTooHigh {
+                fee_bps: self.fee_bps,
+                max_fee_bps,
+            });
+        }
+
+        Ok(Fee {
+            rate: Decimal::from_ratio(self.fee_bps, BPS_DENOMINATOR),
+            recipient: deps.api.addr_validate(&self.address)?,
+        })
+    }
+}
+
+/// The fees taken from the assets of a single zap.
+#[cw_serde]
+#[derive(Default)]
+pub struct Fees {
+    /// The protocol fee, if any
+    pub protocol: Option<Fee>,
+    /// The referral fee, if any
+    pub referral: Option<Fee>,
+}
+
+impl Fees {
+    /// Looks up the protocol fee for `vault` and validates the optional
+    /// referral.
+    pub fn resolve(
+        deps: Deps,
+        vault: Option<&Addr>,
+        referral: Option<&Referral>,
+    ) -> Result<Self, ContractError> {
+        let protocol = FEE_CONFIG
+            .may_load(deps.storage)?
+            .and_then(|c| c.fee_for_vault(vault));
+        let referral = referral
+            .map(|r| r.check(deps))
+            .transpose()?
+            .filter(|fee| !fee.rate.is_zero());
+
+        let total_rate = protocol.as_ref().map(|f| f.rate).unwrap_or_default()
+            + referral.as_ref().map(|f| f.rate).unwrap_or_default();
+        if total_rate >= Decimal::one() {
+            return Err(ContractError::Generic(
+                "Total fee rate must be less than 100%".to_string(),
+            ));
+        }
+
+        Ok(Self { protocol, referral })
+    }
+
+    /// Takes the fees from `assets`. Both fees are calculated on the full
+    /// amount of `assets`. Returns the messages to send the fees, the assets
+    /// remaining after the fees and the `fees` and `referral_fees` event
+    /// attributes with the fee amounts as JSON.
+    pub fn take_from_assets(
+        &self,
+        assets: &AssetList,
+    ) -> StdResult<(Vec<CosmosMsg>, AssetList, Vec<Attribute>)> {
+        let mut msgs = vec![];
+        let mut assets_after_fees = assets.clone();
+        let mut attributes = vec![];
+
+        for (key, fee) in [("fees", &self.protocol), ("referral_fees", &self.referral)] {
+            let fee_amounts = match fee {
+                Some(fee) => {
+                    let (fee_msgs, fee_amounts, _) = fee.take_from_assets(assets)?;
+                    msgs.extend(fee_msgs);
+                    assets_after_fees.deduct_many(&fee_amounts)?;
+                    fee_amounts
+                }
+                None => AssetList::new(),
+            };
+            attributes.push(Attribute::new(
+                key,
+                to_json_binary(&fee_amounts)?.to_string(),
+            ));
+        }
+
+        Ok((msgs, assets_after_fees, attributes))
+    }
+}
@@ -1,11 +1,20 @@
 use cosmwasm_schema::schemars::JsonSchema;
 use cosmwasm_schema::serde::Serialize;
 
-use apollo_cw_asset::AssetInfo;
-use cosmwasm_std::{to_json_binary, CosmosMsg, StdResult, Uint128, WasmMsg};
-use cw_vault_standard::VaultContract;
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use cosmwasm_std::{
+    to_json_binary, Addr, Api, Coin, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env, StdResult,
+    Uint128, WasmMsg,
+};
+use cw_vault_standard::extensions::lockup::LockupExecuteMsg;
+use cw_vault_standard::{ExtensionExecuteMsg, VaultContract, VaultStandardExecuteMsg};
 
-/// A trait to help with depositing an `Asset` into a vault.
+use crate::msg::{CallbackMsg, Pool, ReceiveChoice};
+use crate::state::{ASTROPORT_LIQUIDITY_MANAGER, RESERVED_BALANCES};
+use crate::ContractError;
+
+/// A trait to help with depositing into and withdrawing from vaults with either
+/// native or CW20 tokens.
 pub trait VaultHelper {
     /// Returns a vector of CosmosMsgs that will increase the allowance of the
     /// token if it is a CW20 and deposit the token into the vault.
@@ -15,6 +24,24 @@ pub trait VaultHelper {
         deposit_asset_info: &AssetInfo,
         recipient: Option<String>,
     ) -> StdResult<Vec<CosmosMsg>>;
+
+    /// Returns the `AssetInfo` of the vault token. Like for base tokens, a
+    /// vault token that is a valid address is assumed to be a CW20.
+    fn vault_token_info(&self, api: &dyn Api) -> AssetInfo;
+
+    /// Returns an error unless `vault_tokens` are the vault's vault tokens. A
+    /// CW20 vault token must be issued by the vault contract itself, as CW20
+    /// vault tokens are redeemed and unlocked without a transfer or allowance.
+    fn check_vault_tokens(&self, api: &dyn Api, vault_tokens: &Asset) -> Result<(), ContractError>;
+
+    /// Returns a CosmosMsg that redeems `vault_tokens` held by the caller.
+    /// Native vault tokens are sent along as funds, while CW20 vault tokens
+    /// are burned by the vault from the caller's balance.
+    fn redeem_vault_tokens(&self, vault_tokens: &Asset) -> StdResult<CosmosMsg>;
+
+    /// Returns a CosmosMsg that calls Unlock on the vault's lockup extension
+    /// with `vault_tokens` held by the caller.
+    fn unlock_vault_tokens(&self, vault_tokens: &Asset) -> StdResult<CosmosMsg>;
 }
 
 impl<E, Q> VaultHelper for VaultContract<E, Q>
@@ -48,4 +75,379 @@ where
 
         Ok(msgs)
     }
+
+    fn vault_token_info(&self, api: &dyn Api) -> AssetInfo {
+        match api.addr_validate(&self.vault_token) {
+            Ok(addr) => AssetInfo::cw20(addr),
+            Err(_) => AssetInfo::native(&self.vault_token),
+        }
+    }
+
+    fn check_vault_tokens(&self, api: &dyn Api, vault_tokens: &Asset) -> Result<(), ContractError> {
+        if vault_tokens.info != self.vault_token_info(api) {
+            return Err(ContractError::InvalidVaultToken {});
+        }
+        match &vault_tokens.info {
+            AssetInfo::Cw20(addr) if addr != self.addr => Err(ContractError::InvalidVaultToken {}),
+            _ => Ok(()),
+        }
+    }
+
+    fn redeem_vault_tokens(&self, vault_tokens: &Asset) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: self.addr.to_string(),
+            msg: to_json_binary(&VaultStandardExecuteMsg::<E>::Redeem {
+                recipient: None,
+                amount: vault_tokens.amount,
+            })?,
+            funds: native_funds(vault_tokens),
+        }))
+    }
+
+    fn unlock_vault_tokens(&self, vault_tokens: &Asset) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: self.addr.to_string(),
+            msg: to_json_binary(&VaultStandardExecuteMsg::VaultExtension(
+                ExtensionExecuteMsg::Lockup(LockupExecuteMsg::Unlock {
+                    amount: vault_tokens.amount,
+                }),
+            ))?,
+            funds: native_funds(vault_tokens),
+        }))
+    }
+}
+
+/// Returns `asset` as funds to send along with a message if it is a native
+/// token, or no funds if it is a CW20.
+fn native_funds(asset: &Asset) -> Vec<Coin> {
+    match &asset.info {
+        AssetInfo::Native(denom) => vec![Coin::new(asset.amount.u128(), denom)],
+        AssetInfo::Cw20(_) => vec![],
+    }
+}
+
+/// Splits `assets` according to the weights of `basket`. The last entry of the
+/// basket receives the remainder, so that no dust is left over due to
+/// rounding.
+pub fn split_by_weights<T: Clone>(
+    assets: &AssetList,
+    basket: &[(T, Decimal)],
+) -> StdResult<Vec<(T, AssetList)>> {
+    let mut remaining = assets.clone();
+    let mut parts = vec![];
+
+    for (i, (info, weight)) in basket.iter().enumerate() {
+        let part: AssetList = if i == basket.len() - 1 {
+            remaining.clone()
+        } else {
+            assets
+                .iter()
+                .map(|a| Asset::new(a.info.clone(), a.amount * *weight))
+                .filter(|a| !a.amount.is_zero())
+                .collect::<Vec<_>>()
+                .into()
+        };
+        remaining.deduct_many(&part)?;
+        parts.push((info.clone(), part));
+    }
+
+    Ok(parts)
+}
+
+/// Returns the contract's balances of `asset_infos`, excluding the balances
+/// reserved by `reserve_balances` and for the remaining deposits of a
+/// `BatchDeposit`.
+pub fn query_unreserved_balances(
+    deps: Deps,
+    env: &Env,
+    asset_infos: Vec<AssetInfo>,
+) -> StdResult<AssetList> {
+    let reserved = RESERVED_BALANCES
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let balances =
+        AssetList::query_asset_info_balances(asset_infos, &deps.querier, &env.contract.address)?;
+
+    Ok(balances
+        .into_iter()
+        .map(|asset| {
+            let reserved_amount = reserved
+                .find(&asset.info)
+                .map(|a| a.amount)
+                .unwrap_or_default();
+            Asset::new(
+                asset.info.clone(),
+                asset.amount.saturating_sub(reserved_amount),
+            )
+        })
+        .collect::<Vec<_>>()
+        .into())
+}
+
+/// Reserves the contract's current balances of `asset_infos`, less `held`, the
+/// assets of the operation that the contract already holds, so that the
+/// callbacks of the operation only use the balances that it adds. Returns a
+/// `ReturnLeftovers` message that must be the last message of the operation,
+/// which sends what is left of the added balances to `recipient` and releases
+/// the reservation.
+///
+/// Fails while another operation is in progress, e.g. if a vault calls back
+/// into the contract, as its `ReturnLeftovers` would release the reservation of
+/// the outer operation.
+pub fn reserve_balances(
+    deps: DepsMut,
+    env: &Env,
+    asset_infos: Vec<AssetInfo>,
+    held: &AssetList,
+    recipient: &Addr,
+) -> Result<CosmosMsg, ContractError> {
+    if RESERVED_BALANCES.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::OperationInProgress {});
+    }
+
+    let balances = AssetList::query_asset_info_balances(
+        asset_infos.clone(),
+        &deps.querier,
+        &env.contract.address,
+    )?;
+    let mut reserved = AssetList::new();
+    for balance in &balances {
+        let held_amount = held
+            .find(&balance.info)
+            .map(|a| a.amount)
+            .unwrap_or_default();
+        let amount = balance.amount.saturating_sub(held_amount);
+        if !amount.is_zero() {
+            reserved.add(&Asset::new(balance.info.clone(), amount))?;
+        }
+    }
+    RESERVED_BALANCES.save(deps.storage, &reserved)?;
+
+    Ok(CallbackMsg::ReturnLeftovers {
+        assets: asset_infos,
+        recipient: recipient.clone(),
+    }
+    .into_cosmos_msg(env)?)
+}
+
+/// Returns `base_token` and the assets it is made of: the assets of its pool
+/// and of any nested pools if it is an LP token, or likewise for the base
+/// token of the vault whose vault token it is.
+pub fn base_token_assets(deps: Deps, base_token: &AssetInfo) -> StdResult<Vec<AssetInfo>> {
+    let astroport_liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?;
+    let mut assets: Vec<AssetInfo> = vec![];
+    let mut base_token = base_token.clone();
+    loop {
+        assets.push(base_token.clone());
+
+        let pools = get_nested_pools(deps, &base_token, astroport_liquidity_manager.clone())?;
+        for pool in &pools {
+            for info in pool.pool_assets(deps)? {
+                if !assets.contains(&info) {
+                    assets.push(info);
+                }
+            }
+        }
+
+        // Continue with the base token of the inner vault if the base token is
+        // its vault token
+        let inner_base_token = match get_vault_for_vault_token(deps, &base_token) {
+            Some(inner_vault) if pools.is_empty() => {
+                match deps.api.addr_validate(&inner_vault.base_token) {
+                    Ok(addr) => AssetInfo::cw20(addr),
+                    Err(_) => AssetInfo::native(&inner_vault.base_token),
+                }
+            }
+            _ => break,
+        };
+        if assets.contains(&inner_base_token) {
+            break;
+        }
+        base_token = inner_base_token;
+    }
+
+    Ok(assets)
+}
+
+/// Returns the vault whose vault token is `token`, if any. A native vault token
+/// is expected to be a token factory denom created by the vault and a CW20
+/// vault token is expected to be issued by the vault contract itself.
+pub fn get_vault_for_vault_token(
+    deps: Deps,
+    token: &AssetInfo,
+) -> Option<VaultContract<Empty, Empty>> {
+    let vault_address = match token {
+        AssetInfo::Cw20(addr) => addr.clone(),
+        AssetInfo::Native(denom) => match denom.splitn(3, '/').collect::<Vec<_>>()[..] {
+            ["factory", creator, _] => deps.api.addr_validate(creator).ok()?,
+            _ => return None,
+        },
+    };
+
+    let vault: VaultContract<Empty, Empty> =
+        VaultContract::new(&deps.querier, &vault_address).ok()?;
+    (vault.vault_token_info(deps.api) == *token).then_some(vault)
+}
+
+/// An inner vault to redeem through, its base token and the receive choice to
+/// use for that base token.
+pub type InnerVaultRedemption = (VaultContract<Empty, Empty>, AssetInfo, ReceiveChoice);
+
+/// If `base_token` is the vault token of another vault and `receive_choice`
+/// requires redeeming it, returns that inner vault, its base token and the
+/// receive choice to use for the inner vault's base token.
+///
+/// `ReceiveChoice::Underlying` is kept if the inner vault's base token can be
+/// unwrapped further and becomes `ReceiveChoice::BaseToken` otherwise.
+pub fn get_inner_vault_redemption(
+    deps: Deps,
+    base_token: &AssetInfo,
+    receive_choice: &ReceiveChoice,
+    astroport_liquidity_manager: Option<Addr>,
+) -> Result<Option<InnerVaultRedemption>, ContractError> {
+    match receive_choice {
+        ReceiveChoice::BaseToken => return Ok(None),
+        ReceiveChoice::SwapTo(requested_asset) if requested_asset == base_token => return Ok(None),
+        _ => {}
+    }
+    let inner_vault = match get_vault_for_vault_token(deps, base_token) {
+        Some(inner_vault) => inner_vault,
+        None => return Ok(None),
+    };
+    let inner_base_token = match deps.api.addr_validate(&inner_vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&inner_vault.base_token),
+    };
+
+    let receive_choice = match receive_choice {
+        ReceiveChoice::Underlying => {
+            let can_unwrap =
+                !get_nested_pools(deps, &inner_base_token, astroport_liquidity_manager)?.is_empty()
+                    || get_vault_for_vault_token(deps, &inner_base_token).is_some();
+            if can_unwrap {
+                ReceiveChoice::Underlying
+            } else {
+                ReceiveChoice::BaseToken
+            }
+        }
+        ReceiveChoice::Basket(basket) if basket.iter().any(|(info, _)| info == base_token) => {
+            return Err(ContractError::InvalidBasket {
+                reason: "can not contain the base token when it is a vault token".to_string(),
+            });
+        }
+        receive_choice => receive_choice.clone(),
+    };
+
+    Ok(Some((inner_vault, inner_base_token, receive_choice)))
+}
+
+/// The maximum number of pools that an LP token is decomposed through. E.g. an
+/// LP token of a pool containing another LP token is decomposed through two
+/// pools.
+pub const MAX_NESTED_POOLS: usize = 3;
+
+/// Returns the pools that `lp_token` decomposes into, outermost first. Each
+/// next pool is the pool of the first asset of the previous pool that is
+/// itself an LP token, up to `MAX_NESTED_POOLS` pools. Returns an empty vec if
+/// `lp_token` is not an LP token.
+pub fn get_nested_pools(
+    deps: Deps,
+    lp_token: &AssetInfo,
+    astroport_liquidity_manager: Option<Addr>,
+) -> StdResult<Vec<Pool>> {
+    let mut pools = vec![];
+    let mut next =
+        Pool::get_pool_for_lp_token(deps, lp_token, astroport_liquidity_manager.clone()).ok();
+
+    while let Some(pool) = next {
+        next = if pools.len() + 1 < MAX_NESTED_POOLS {
+            pool.pool_assets(deps)?.iter().find_map(|info| {
+                Pool::get_pool_for_lp_token(deps, info, astroport_liquidity_manager.clone()).ok()
+            })
+        } else {
+            None
+        };
+        pools.push(pool);
+    }
+
+    Ok(pools)
+}
+
+/// Estimates the assets received when withdrawing liquidity with `lp_token`
+/// from the first of `pools` and then from each of the inner pools in turn, as
+/// in `callback_after_withdraw_liq`. LP tokens in `keep` are not withdrawn.
+pub fn simulate_withdraw_nested_liquidity(
+    deps: Deps,
+    pools: &[Pool],
+    lp_token: &Asset,
+    keep: &[AssetInfo],
+) -> StdResult<AssetList> {
+    let mut assets = pools[0].simulate_withdraw_liquidity(deps, lp_token)?;
+
+    for pool in &pools[1..] {
+        let inner_lp_token = match assets.find(&pool.lp_token()) {
+            Some(asset) if !asset.amount.is_zero() && !keep.contains(&asset.info) => asset.clone(),
+            _ => break,
+        };
+        assets.deduct(&inner_lp_token)?;
+        assets.add_many(&pool.simulate_withdraw_liquidity(deps, &inner_lp_token)?)?;
+    }
+
+    Ok(assets)
+}
+
+/// Estimates the amount of LP tokens received when providing `assets` to
+/// `pool` via the liquidity helper's `balancing_provide_liquidity`.
+///
+/// The liquidity helper first swaps part of the asset that is in excess of the
+/// pool ratio into the other asset. For two-asset pools this is approximated by
+/// simulating a swap of half of the excess before simulating the provide.
+pub fn simulate_balancing_provide_liquidity(
+    deps: Deps,
+    env: &Env,
+    pool: &Pool,
+    assets: &AssetList,
+) -> StdResult<Uint128> {
+    let reserves = pool.get_pool_liquidity(deps)?.to_vec();
+
+    // Include every pool asset, even those with zero amount, in pool order
+    let mut amounts: Vec<Asset> = reserves
+        .iter()
+        .map(|r| {
+            let amount = assets.find(&r.info).map(|a| a.amount).unwrap_or_default();
+            Asset::new(r.info.clone(), amount)
+        })
+        .collect();
+
+    if let [r0, r1] = reserves.as_slice() {
+        if !r0.amount.is_zero() && !r1.amount.is_zero() {
+            let (a0, a1) = (amounts[0].amount, amounts[1].amount);
+
+            // Find the asset in excess of the pool ratio and how much of it
+            let excess = if a0.full_mul(r1.amount) > a1.full_mul(r0.amount) {
+                Some((0, 1, a0 - a1.multiply_ratio(r0.amount, r1.amount)))
+            } else if a1.full_mul(r0.amount) > a0.full_mul(r1.amount) {
+                Some((1, 0, a1 - a0.multiply_ratio(r1.amount, r0.amount)))
+            } else {
+                None
+            };
+
+            if let Some((offer_idx, ask_idx, excess_amount)) = excess {
+                let offer_amount = excess_amount / Uint128::new(2);
+                if !offer_amount.is_zero() {
+                    let return_amount = pool.simulate_swap(
+                        deps,
+                        Asset::new(amounts[offer_idx].info.clone(), offer_amount),
+                        amounts[ask_idx].info.clone(),
+                    )?;
+                    amounts[offer_idx].amount -= offer_amount;
+                    amounts[ask_idx].amount += return_amount;
+                }
+            }
+        }
+    }
+
+    Ok(pool
+        .simulate_provide_liquidity(deps, env, amounts.into())?
+        .amount)
 }
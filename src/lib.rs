@@ -1,11 +1,15 @@
+pub mod config;
 pub mod contract;
 pub mod deposit;
 pub mod error;
+pub mod fees;
 pub mod helpers;
 pub mod lockup;
 pub mod msg;
+pub mod oracle;
 pub mod query;
 pub mod state;
+pub mod vault_migration;
 pub mod withdraw;
 
 pub use crate::error::ContractError;
@@ -1,52 +1,50 @@
-use cosmwasm_std::{
-    to_json_binary, Addr, CosmosMsg, DepsMut, Empty, Env, MessageInfo, ReplyOn, Response, SubMsg,
-    WasmMsg,
-};
-use cw_vault_standard::extensions::lockup::LockupExecuteMsg;
-use cw_vault_standard::{
-    ExtensionExecuteMsg, ExtensionQueryMsg, VaultInfoResponse, VaultStandardExecuteMsg,
-    VaultStandardQueryMsg,
-};
+use apollo_cw_asset::Asset;
+use cosmwasm_std::{Addr, DepsMut, Empty, Env, Event, MessageInfo, ReplyOn, Response, SubMsg};
+use cw_vault_standard::VaultContract;
 
 use crate::contract::UNLOCK_REPLY_ID;
-use crate::state::TEMP_LOCK_KEY;
+use crate::helpers::VaultHelper;
+use crate::state::{LOCKUP_IDS, TEMP_LOCK_KEY};
 use crate::ContractError;
 
 pub fn execute_unlock(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     vault_address: Addr,
+) -> Result<Response, ContractError> {
+    // Make sure exactly one native token was sent. That it is the vault token is
+    // checked in `unlock`.
+    if info.funds.len() != 1 {
+        return Err(ContractError::InvalidVaultToken {});
+    }
+    let vault_tokens = Asset::native(&info.funds[0].denom, info.funds[0].amount);
+
+    unlock(deps, env, info.sender, vault_address, vault_tokens)
+}
+
+/// Unlocks `vault_tokens`, which must already be held by the contract, on
+/// behalf of `sender`. Called by `execute_unlock` for native vault tokens and
+/// by the CW20 receive hook for CW20 vault tokens.
+pub fn unlock(
+    deps: DepsMut,
+    _env: Env,
+    sender: Addr,
+    vault_address: Addr,
+    vault_tokens: Asset,
 ) -> Result<Response, ContractError> {
     // Query the vault info
-    let vault_info: VaultInfoResponse = deps.querier.query_wasm_smart(
-        vault_address.to_string(),
-        &VaultStandardQueryMsg::<ExtensionQueryMsg>::Info {},
-    )?;
-    let vault_token_denom = vault_info.vault_token;
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
 
     // Make sure vault token was sent
-    if info.funds.len() != 1 || info.funds[0].denom != vault_token_denom {
-        return Err(ContractError::InvalidVaultToken {});
-    }
-    let vault_token = info.funds[0].clone();
+    vault.check_vault_tokens(deps.api, &vault_tokens)?;
 
     // Call unlock on the vault
-    let unlock_msg: CosmosMsg<Empty> = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: vault_address.to_string(),
-        funds: vec![vault_token.clone()],
-        msg: to_json_binary(
-            &VaultStandardExecuteMsg::<ExtensionExecuteMsg>::VaultExtension(
-                ExtensionExecuteMsg::Lockup(LockupExecuteMsg::Unlock {
-                    amount: vault_token.amount,
-                }),
-            ),
-        )?,
-    });
+    let unlock_msg = vault.unlock_vault_tokens(&vault_tokens)?;
 
     // Temporarily store the caller's address and the vault address so we can read
     // it in the reply entrypoint
-    TEMP_LOCK_KEY.save(deps.storage, &(info.sender, vault_address))?;
+    TEMP_LOCK_KEY.save(deps.storage, &(sender, vault_address))?;
 
     // We must add the unlock message as a submessage and parse the Lock ID in the
     // reply entrypoint.
@@ -57,3 +55,36 @@ pub fn execute_unlock(
         reply_on: ReplyOn::Success,
     }))
 }
+
+/// Transfers the sender's unlocking position `lockup_id` in the vault to
+/// `new_owner`, who can then withdraw from it instead of the sender.
+pub fn execute_transfer_unlocking_position(
+    deps: DepsMut,
+    info: MessageInfo,
+    vault_address: Addr,
+    lockup_id: u64,
+    new_owner: Addr,
+) -> Result<Response, ContractError> {
+    let key = LOCKUP_IDS.key((info.sender.clone(), vault_address.clone(), lockup_id));
+
+    // Check that the sender owns the unlocking position
+    if !key.has(deps.storage) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Re-key the lockup ID under the new owner
+    key.remove(deps.storage);
+    LOCKUP_IDS.save(
+        deps.storage,
+        (new_owner.clone(), vault_address.clone(), lockup_id),
+        &(),
+    )?;
+
+    let event = Event::new("apollo/vault-zapper/transfer_unlocking_position")
+        .add_attribute("vault_address", &vault_address)
+        .add_attribute("lockup_id", lockup_id.to_string())
+        .add_attribute("from", &info.sender)
+        .add_attribute("to", &new_owner);
+
+    Ok(Response::new().add_event(event))
+}
@@ -1,12 +1,20 @@
 use std::ops::Deref;
 
-use apollo_cw_asset::{AssetInfo, AssetList, AssetListUnchecked, AssetUnchecked};
+use apollo_cw_asset::{
+    AssetInfo, AssetInfoUnchecked, AssetList, AssetListBase, AssetListUnchecked, AssetUnchecked,
+};
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{to_json_binary, Addr, CosmosMsg, Deps, Env, StdResult, Uint128, WasmMsg};
+use cosmwasm_std::{
+    to_json_binary, Addr, Api, CosmosMsg, Decimal, Deps, Env, StdResult, Uint128, WasmMsg,
+};
+use cw20::Cw20ReceiveMsg;
+use cw_address_like::AddressLike;
 use cw_dex::traits::Pool as PoolTrait;
 use cw_dex::CwDexError;
-use cw_dex_router::helpers::CwDexRouterUnchecked;
-use liquidity_helper::LiquidityHelperUnchecked;
+use cw_dex_router::helpers::{CwDexRouter, CwDexRouterUnchecked};
+use cw_ownable::{cw_ownable_execute, cw_ownable_query};
+use cw_vault_standard::extensions::lockup::UnlockingPosition;
+use liquidity_helper::{LiquidityHelper, LiquidityHelperUnchecked};
 
 #[cfg(feature = "astroport")]
 use cw_dex_astroport::AstroportPool;
@@ -14,6 +22,8 @@ use cw_dex_astroport::AstroportPool;
 #[cfg(feature = "osmosis")]
 use cw_dex_osmosis::OsmosisPool;
 
+use crate::fees::{FeeConfig, Fees, Referral};
+use crate::oracle::OracleConfig;
 use crate::ContractError;
 
 /// An enum with all known variants that implement the cw-dex Pool trait.
@@ -84,14 +94,36 @@ impl Pool {
 
 #[cw_serde]
 pub struct InstantiateMsg {
+    /// The owner of the contract, who is allowed to update the config
+    pub owner: String,
     pub router: CwDexRouterUnchecked,
     pub liquidity_helper: LiquidityHelperUnchecked,
     /// The address of the `astroport-liquidity-manager` contract. Only needed
     /// if the `astroport` feature flag is enabled.
     #[cfg(feature = "astroport")]
     pub astroport_liquidity_manager: String,
+    /// The protocol fee configuration. If not set, no fees are taken.
+    pub fee_config: Option<FeeConfig<String>>,
 }
 
+/// Updates to the contract config. Fields set to `None` are left unchanged.
+#[cw_serde]
+#[derive(Default)]
+pub struct ConfigUpdates {
+    pub router: Option<CwDexRouterUnchecked>,
+    pub liquidity_helper: Option<LiquidityHelperUnchecked>,
+    /// The address of the `astroport-liquidity-manager` contract. Only
+    /// available if the `astroport` feature flag is enabled.
+    #[cfg(feature = "astroport")]
+    pub astroport_liquidity_manager: Option<String>,
+    pub fee_config: Option<FeeConfig<String>>,
+    /// The maximum referral fee in basis points that callers may set
+    pub max_referral_fee_bps: Option<u16>,
+    /// The price oracle configuration to check swaps and zaps against
+    pub oracle_config: Option<OracleConfig<String>>,
+}
+
+#[cw_ownable_execute]
 #[cw_serde]
 pub enum ExecuteMsg {
     /// Deposit assets into a vault
@@ -105,6 +137,34 @@ pub enum ExecuteMsg {
         /// The minimum amount of vault tokens to receive. If the amount of
         /// vault tokens received is less than this, the transaction will fail.
         min_out: Uint128,
+        /// The maximum allowed slippage, relative to an on-chain simulation of
+        /// the deposit at execution time. If set, the minimum amount of vault
+        /// tokens to receive is the larger of `min_out` and the simulated
+        /// amount reduced by `max_slippage`.
+        ///
+        /// The simulation sees the pool prices of the same transaction, so
+        /// this only guards against the price impact of the deposit itself and
+        /// not against prices that were moved before it, e.g. by a sandwich
+        /// attack. Callers must still set `min_out` from an off-chain quote to
+        /// be protected against MEV.
+        max_slippage: Option<Decimal>,
+        /// An optional referral that receives a share of the zapped assets
+        referral: Option<Referral>,
+        /// Optional minimum amounts to receive from the individual swaps and
+        /// liquidity provision of the deposit
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Split the sent assets across several vaults by weight and deposit each
+    /// part into its vault. There are no `leg_min_outs` as the legs span
+    /// several vaults; the `min_out` of each entry still applies.
+    BatchDeposit {
+        /// The vaults to deposit into, with the fraction of the assets to
+        /// deposit into each. The weights must be positive and sum to 1.
+        deposits: Vec<BatchDepositEntry>,
+        /// The assets to deposit
+        assets: AssetListUnchecked,
+        /// The recipient of the vault tokens
+        recipient: Option<String>,
     },
     /// Redeem vault tokens and optionally swap the redeemed assets to other
     /// assets
@@ -118,6 +178,27 @@ pub enum ExecuteMsg {
         /// The minimum amount of assets to receive. If the amount of assets
         /// received is less than this, the transaction will fail.
         min_out: AssetListUnchecked,
+        /// An optional referral that receives a share of the zapped assets
+        referral: Option<Referral>,
+        /// Optional minimum amounts to receive from the individual liquidity
+        /// withdrawal and swaps
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Redeem the vault tokens of several vaults, sent as one coin per vault,
+    /// and receive the combined redeemed assets as a single `ReceiveChoice`.
+    /// Only native vault tokens are accepted. There are no `leg_min_outs` as
+    /// the legs span several vaults; only `min_out` is enforced, on the
+    /// combined assets.
+    BatchRedeem {
+        /// The addresses of the vaults to redeem from
+        redemptions: Vec<String>,
+        /// The recipient of the redeemed assets
+        recipient: Option<String>,
+        /// The choice of which asset(s) to receive from each vault
+        receive_choice: ReceiveChoice,
+        /// The minimum amount of assets to receive in total. If the amount of
+        /// assets received is less than this, the transaction will fail.
+        min_out: AssetListUnchecked,
     },
     /// Zap a vault's base token to other assets
     ZapBaseTokens {
@@ -130,6 +211,11 @@ pub enum ExecuteMsg {
         /// The minimum amount of assets to receive. If the amount of assets
         /// received is less than this, the transaction will fail.
         min_out: AssetListUnchecked,
+        /// An optional referral that receives a share of the zapped assets
+        referral: Option<Referral>,
+        /// Optional minimum amounts to receive from the individual liquidity
+        /// withdrawal and swaps
+        leg_min_outs: Option<LegMinOutsUnchecked>,
     },
     /// Call unlock on the specified vault and burn the sent vault tokens to
     /// create an unlocking position. The unlocking position can be withdrawn
@@ -138,6 +224,16 @@ pub enum ExecuteMsg {
         /// The address of the vault to call unlock on
         vault_address: String,
     },
+    /// Transfer an unlocking position created with Unlock to another address,
+    /// which can then withdraw from it instead of the sender
+    TransferUnlockingPosition {
+        /// The address of the vault the position is unlocking in
+        vault_address: String,
+        /// The ID of the unlocking position to transfer
+        lockup_id: u64,
+        /// The address to transfer the unlocking position to
+        new_owner: String,
+    },
     WithdrawUnlocked {
         /// The address of the vault to withdraw from
         vault_address: String,
@@ -150,11 +246,189 @@ pub enum ExecuteMsg {
         /// The minimum amount of assets to receive. If the amount of assets
         /// received is less than this, the transaction will fail.
         min_out: AssetListUnchecked,
+        /// An optional referral that receives a share of the zapped assets
+        referral: Option<Referral>,
+        /// Optional minimum amounts to receive from the individual liquidity
+        /// withdrawal and swaps
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Withdraw from several unlocking positions that have finished unlocking
+    /// and receive the combined withdrawn assets as a single `ReceiveChoice`.
+    /// There are no `leg_min_outs` as the legs span several positions; only
+    /// `min_out` is enforced, on the combined assets.
+    WithdrawUnlockedMany {
+        /// The unlocking positions to withdraw from, as (vault address, lockup
+        /// id)
+        positions: Vec<(String, u64)>,
+        /// The recipient of the withdrawn assets
+        recipient: Option<String>,
+        /// The choice of which asset(s) to receive
+        receive_choice: ReceiveChoice,
+        /// The minimum amount of assets to receive in total. If the amount of
+        /// assets received is less than this, the transaction will fail.
+        min_out: AssetListUnchecked,
+    },
+    /// Withdraw from all of the sender's unlocking positions that have
+    /// finished unlocking and receive the combined withdrawn assets as a single
+    /// `ReceiveChoice`. At most `limit` unlocking positions, matured or not,
+    /// are considered per call, paginated like the UserUnlockingPositions
+    /// query. Like `WithdrawUnlockedMany`, there are no `leg_min_outs`.
+    WithdrawAllMatured {
+        /// Only withdraw from unlocking positions of this vault, if set
+        vault_address: Option<String>,
+        /// The recipient of the withdrawn assets
+        recipient: Option<String>,
+        /// The choice of which asset(s) to receive
+        receive_choice: ReceiveChoice,
+        /// The minimum amount of assets to receive in total. If the amount of
+        /// assets received is less than this, the transaction will fail.
+        min_out: AssetListUnchecked,
+        /// Only consider unlocking positions after this vault. Ignored if
+        /// `vault_address` is set.
+        start_after_vault_addr: Option<String>,
+        /// Only consider unlocking positions after this lockup id
+        start_after_id: Option<u64>,
+        /// The maximum number of unlocking positions to consider. Defaults to
+        /// 10.
+        limit: Option<u32>,
+    },
+    /// Withdraw from an unlocking position that has finished unlocking and
+    /// deposit the withdrawn base tokens into another vault, converting them
+    /// into that vault's base token if needed.
+    WithdrawUnlockedToVault {
+        /// The address of the vault to withdraw from
+        vault_address: String,
+        /// The ID of the unlocking position to withdraw from
+        lockup_id: u64,
+        /// The address of the vault to deposit into
+        to_vault: String,
+        /// The recipient of the vault tokens of `to_vault`
+        recipient: Option<String>,
+        /// The minimum amount of vault tokens of `to_vault` to receive. If the
+        /// amount of vault tokens received is less than this, the transaction
+        /// will fail.
+        min_out: Uint128,
+        /// Optional minimum amounts to receive from the first liquidity
+        /// withdrawal of the redeemed base tokens and from the swaps and
+        /// liquidity provision of the deposit
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Redeem the sent vault tokens from one vault and deposit the redeemed
+    /// base tokens into another vault. If the base tokens differ, the
+    /// redeemed base token is converted into the other vault's base token.
+    MigrateBetweenVaults {
+        /// The address of the vault to redeem the sent vault tokens from
+        from_vault: String,
+        /// The address of the vault to deposit into
+        to_vault: String,
+        /// The recipient of the vault tokens of `to_vault`
+        recipient: Option<String>,
+        /// The minimum amount of vault tokens of `to_vault` to receive. If the
+        /// amount of vault tokens received is less than this, the transaction
+        /// will fail.
+        min_out: Uint128,
+        /// Optional minimum amounts to receive from the first liquidity
+        /// withdrawal of the redeemed base tokens and from the swaps and
+        /// liquidity provision of the deposit
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Receive hook for CW20 tokens sent with `Cw20ExecuteMsg::Send`. The
+    /// embedded message must be a `Cw20HookMsg`.
+    Receive(Cw20ReceiveMsg),
+    /// Update the config of the contract. Can only be called by the owner.
+    UpdateConfig {
+        /// The config updates
+        updates: ConfigUpdates,
+    },
+    /// Send assets stranded in the contract, e.g. dust or tokens sent to it
+    /// directly, to the recipient. Can only be called by the owner and not
+    /// while an operation is in progress.
+    Sweep {
+        /// The assets to send
+        assets: AssetListUnchecked,
+        /// The recipient of the assets. Defaults to the owner.
+        recipient: Option<String>,
     },
     /// Messages that can only be called by the contract itself.
     Callback(CallbackMsg),
 }
 
+/// Messages that can be embedded in a `Cw20ExecuteMsg::Send` to the zapper. The
+/// sent CW20 tokens are used as the assets of the zap.
+#[cw_serde]
+pub enum Cw20HookMsg {
+    /// Deposit the sent CW20 tokens into a vault. See `ExecuteMsg::Deposit`.
+    Deposit {
+        /// The address of the vault to deposit into
+        vault_address: String,
+        /// The recipient of the vault tokens
+        recipient: Option<String>,
+        /// The minimum amount of vault tokens to receive
+        min_out: Uint128,
+        /// The maximum allowed slippage relative to an on-chain simulation.
+        /// Only guards against the deposit's own price impact, see
+        /// `ExecuteMsg::Deposit`.
+        max_slippage: Option<Decimal>,
+        /// An optional referral that receives a share of the zapped assets
+        referral: Option<Referral>,
+        /// Optional minimum amounts to receive from the individual legs
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Redeem the sent CW20 vault tokens from the vault. See
+    /// `ExecuteMsg::Redeem`.
+    Redeem {
+        /// The address of the vault to redeem from. The sent CW20 must be the
+        /// vault token of this vault.
+        vault_address: String,
+        /// The recipient of the redeemed assets
+        recipient: Option<String>,
+        /// The choice of which asset(s) to receive
+        receive_choice: ReceiveChoice,
+        /// The minimum amount of assets to receive
+        min_out: AssetListUnchecked,
+        /// An optional referral that receives a share of the zapped assets
+        referral: Option<Referral>,
+        /// Optional minimum amounts to receive from the individual legs
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Call unlock on the vault with the sent CW20 vault tokens. See
+    /// `ExecuteMsg::Unlock`.
+    Unlock {
+        /// The address of the vault to call unlock on. The sent CW20 must be
+        /// the vault token of this vault.
+        vault_address: String,
+    },
+    /// Zap the sent CW20 tokens, which must be a vault's base token, to other
+    /// assets. See `ExecuteMsg::ZapBaseTokens`.
+    ZapBaseTokens {
+        /// The recipient of the redeemed assets
+        recipient: Option<String>,
+        /// The asset to swap to
+        receive_choice: ReceiveChoice,
+        /// The minimum amount of assets to receive
+        min_out: AssetListUnchecked,
+        /// An optional referral that receives a share of the zapped assets
+        referral: Option<Referral>,
+        /// Optional minimum amounts to receive from the individual legs
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+    /// Migrate the sent CW20 vault tokens to another vault. See
+    /// `ExecuteMsg::MigrateBetweenVaults`.
+    MigrateBetweenVaults {
+        /// The address of the vault to redeem from. The sent CW20 must be the
+        /// vault token of this vault.
+        from_vault: String,
+        /// The address of the vault to deposit into
+        to_vault: String,
+        /// The recipient of the vault tokens of `to_vault`
+        recipient: Option<String>,
+        /// The minimum amount of vault tokens of `to_vault` to receive
+        min_out: Uint128,
+        /// Optional minimum amounts to receive from the individual legs
+        leg_min_outs: Option<LegMinOutsUnchecked>,
+    },
+}
+
 #[cw_serde]
 pub enum CallbackMsg {
     /// Provide liquidity to a pool
@@ -167,7 +441,12 @@ pub enum CallbackMsg {
         pool: Pool,
         /// The asset info of the vault's deposit asset
         deposit_asset_info: AssetInfo,
+        /// The minimum amount of LP tokens to receive
+        min_out: Uint128,
     },
+    /// Provide the contract's balances of the pool's assets as liquidity to
+    /// an inner pool of a nested LP token, without depositing into a vault
+    ProvideInnerLiquidity { pool: Pool },
     /// Performs the actual deposit into the vault
     Deposit {
         vault_address: Addr,
@@ -187,19 +466,82 @@ pub enum CallbackMsg {
         /// of any of the assets is less than this, the transaction will
         /// fail.
         min_out: AssetList,
+        /// The assets given in exchange for the received assets. If set, the
+        /// value of the received assets is checked against their value at
+        /// oracle prices.
+        offer_assets: AssetList,
     },
     /// Called after redeeming vault tokens
     AfterRedeem {
         receive_choice: ReceiveChoice,
         vault_base_token: AssetInfo,
         recipient: Addr,
-        min_out: AssetList,
+        /// The minimum amount of assets to receive, or `None` for the
+        /// redemptions of a batch, whose minimum amount is enforced on the
+        /// combined assets instead
+        min_out: Option<AssetList>,
+        /// The fees to take from the outgoing assets
+        fees: Fees,
+        /// The minimum amounts to receive from the individual legs
+        leg_min_outs: LegMinOuts,
     },
     /// Called after withdrawing liquidity from a pool
     AfterWithdrawLiq {
         assets: Vec<AssetInfo>,
         receive_choice: ReceiveChoice,
         recipient: Addr,
+        /// The fees to take from the withdrawn assets
+        fees: Fees,
+        /// The pools of nested LP tokens among `assets` to withdraw liquidity
+        /// from next, outermost first
+        inner_pools: Vec<Pool>,
+        /// The minimum amount of each asset to receive from swaps into it
+        swap_min_outs: AssetList,
+    },
+    /// Called after withdrawing base tokens from a vault to deposit them into
+    /// another vault
+    ZapIntoVault {
+        /// The base token withdrawn from the previous vault
+        base_token: AssetInfo,
+        /// The vault to deposit into
+        vault_address: Addr,
+        /// The recipient of the vault tokens
+        recipient: Addr,
+        /// The minimum amount of vault tokens to receive
+        min_out: Uint128,
+        /// The minimum amounts to receive from the individual legs
+        leg_min_outs: LegMinOuts,
+    },
+    /// Deposit the contract's balances of the specified assets into a vault
+    DepositBalances {
+        assets: Vec<AssetInfo>,
+        vault_address: Addr,
+        recipient: Addr,
+        min_out: Uint128,
+        /// Inner pools to withdraw liquidity from before depositing, outermost
+        /// first, if one of the assets is the LP token of the first of them
+        inner_pools: Vec<Pool>,
+        /// The minimum amounts to receive from the swaps and liquidity
+        /// provision of the deposit
+        leg_min_outs: LegMinOuts,
+    },
+    /// Deposit the first of the remaining parts of a `BatchDeposit` and
+    /// continue with the rest afterwards
+    BatchDeposit {
+        /// The remaining vaults to deposit into with the assets to deposit
+        /// into each and the minimum amount of vault tokens to receive
+        deposits: Vec<(Addr, AssetList, Uint128)>,
+        /// The recipient of the vault tokens
+        recipient: Addr,
+    },
+    /// Send the contract's unreserved balances of the assets, e.g. rounding
+    /// leftovers of a deposit, to the recipient and release the balances
+    /// reserved by `reserve_balances`
+    ReturnLeftovers {
+        /// The assets to return leftovers of
+        assets: Vec<AssetInfo>,
+        /// The address to return the leftovers to
+        recipient: Addr,
     },
 }
 
@@ -213,6 +555,7 @@ impl CallbackMsg {
     }
 }
 
+#[cw_ownable_query]
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
@@ -247,10 +590,193 @@ pub enum QueryMsg {
         start_after_id: Option<u64>,
         limit: Option<u32>,
     },
+
+    /// Returns AnnotatedUnlockingPositionsResponse. Like
+    /// UserUnlockingPositions, but each position is annotated with whether it
+    /// can be withdrawn at the current block, how long until it can be and an
+    /// estimate of its value. `limit` is the maximum number of positions
+    /// returned after filtering by `claimable_only`. At most
+    /// `MAX_SCANNED_UNLOCKING_POSITIONS` positions are scanned per query, so
+    /// fewer positions may be returned; continue from the returned
+    /// `start_after` until it is `None`.
+    #[returns(AnnotatedUnlockingPositionsResponse)]
+    AnnotatedUserUnlockingPositions {
+        owner: String,
+        start_after_vault_addr: Option<String>,
+        start_after_id: Option<u64>,
+        limit: Option<u32>,
+        /// Only return positions that can be withdrawn at the current block
+        claimable_only: Option<bool>,
+    },
+
+    /// Returns SimulateDepositResponse. Estimates the amount of vault tokens
+    /// that would be received when calling Deposit with the given assets.
+    #[returns(SimulateDepositResponse)]
+    SimulateDeposit {
+        assets: AssetListUnchecked,
+        vault_address: String,
+        /// The referral that would be passed to Deposit, if any
+        referral: Option<Referral>,
+    },
+
+    /// Returns AssetList. Estimates the assets that the recipient would
+    /// receive when calling Redeem with the given amount of vault tokens and
+    /// receive choice.
+    #[returns(AssetList)]
+    SimulateRedeem {
+        vault_address: String,
+        vault_token_amount: Uint128,
+        receive_choice: ReceiveChoice,
+        /// The referral that would be passed to Redeem, if any
+        referral: Option<Referral>,
+    },
+
+    /// Returns ConfigResponse. The current config of the contract.
+    #[returns(ConfigResponse)]
+    Config {},
+
+    /// Returns Option<FeeConfig<Addr>>. The protocol fee configuration, if
+    /// any.
+    #[returns(Option<FeeConfig<Addr>>)]
+    FeeConfig {},
+
+    /// Returns Option<OracleConfig<Addr>>. The price oracle configuration, if
+    /// any.
+    #[returns(Option<OracleConfig<Addr>>)]
+    OracleConfig {},
+
+    /// Returns AssetList. The contract's balances of the given assets, e.g. to
+    /// find stranded assets to recover with Sweep.
+    #[returns(AssetList)]
+    ContractBalances {
+        asset_infos: Vec<AssetInfoUnchecked>,
+    },
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct MigrateMsg {
+    /// The owner to set if the contract does not have one yet, i.e. when
+    /// migrating from a version from before owners were introduced.
+    pub owner: Option<String>,
+    /// Config updates to apply as part of the migration
+    pub config_updates: Option<ConfigUpdates>,
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+/// The response type of the Config query
+pub struct ConfigResponse {
+    pub router: CwDexRouter,
+    pub liquidity_helper: LiquidityHelper,
+    /// The address of the `astroport-liquidity-manager` contract. Only set if
+    /// the `astroport` feature flag is enabled.
+    pub astroport_liquidity_manager: Option<Addr>,
+    /// The maximum referral fee in basis points
+    pub max_referral_fee_bps: u16,
+}
+
+#[cw_serde]
+/// The response type of the SimulateDeposit query
+pub struct SimulateDepositResponse {
+    /// The estimated amount of vault tokens received
+    pub vault_token_amount: Uint128,
+    /// The estimated amount of the vault's base token deposited into the vault
+    pub base_token_amount: Uint128,
+    /// The estimated assets held by the zapper after the deposited assets
+    /// have been basket liquidated, before liquidity is provided
+    pub liquidated_assets: AssetList,
+}
+
+#[cw_serde]
+/// The response type of the AnnotatedUserUnlockingPositions query.
+pub struct AnnotatedUnlockingPositionsResponse {
+    /// The annotated unlocking positions
+    pub positions: Vec<AnnotatedUnlockingPosition>,
+    /// The vault address and lockup ID of the last scanned position, to pass
+    /// as `start_after_vault_addr` and `start_after_id` to continue the query.
+    /// `None` if all of the user's positions have been scanned.
+    pub start_after: Option<(Addr, u64)>,
+}
+
+#[cw_serde]
+/// An unlocking position annotated with its status at the current block.
+pub struct AnnotatedUnlockingPosition {
+    /// The vault the position is unlocking in
+    pub vault_address: Addr,
+    /// The unlocking position as returned by the vault
+    pub position: UnlockingPosition,
+    /// Whether the position has finished unlocking and can be withdrawn
+    pub is_claimable: bool,
+    /// The number of seconds until the position can be withdrawn, if it is
+    /// released at a time
+    pub seconds_remaining: Option<u64>,
+    /// The number of blocks until the position can be withdrawn, if it is
+    /// released at a block height
+    pub blocks_remaining: Option<u64>,
+    /// The estimated assets received when withdrawing the position as its
+    /// underlying assets, after fees. Just the base token if the base token
+    /// does not wrap other assets. The amount of base tokens of the position,
+    /// before fees, is `position.base_token_amount`.
+    pub estimated_underlying_assets: AssetList,
+}
+
+/// Minimum amounts to receive from the individual legs of a zap, in addition
+/// to the `min_out` of the whole zap. This makes the zap fail at the leg where
+/// e.g. a swap is sandwiched, rather than only at the end.
+#[cw_serde]
+pub struct LegMinOutsBase<T: AddressLike> {
+    /// The minimum amount of each asset to receive from the router swaps into
+    /// it. Swaps into assets not in the list have no minimum.
+    pub swap: AssetListBase<T>,
+    /// The minimum amount of LP tokens to receive when providing liquidity to
+    /// the pool of the vault's base token
+    pub provide_liquidity: Uint128,
+    /// The minimum amount of each asset to receive when withdrawing liquidity
+    /// from the pool of the vault's base token
+    pub withdraw_liquidity: AssetListBase<T>,
+}
+
+pub type LegMinOutsUnchecked = LegMinOutsBase<String>;
+pub type LegMinOuts = LegMinOutsBase<Addr>;
+
+impl LegMinOutsUnchecked {
+    /// Validates the CW20 addresses and returns a `LegMinOuts`.
+    pub fn check(&self, api: &dyn Api) -> StdResult<LegMinOuts> {
+        Ok(LegMinOuts {
+            swap: self.swap.check(api)?,
+            provide_liquidity: self.provide_liquidity,
+            withdraw_liquidity: self.withdraw_liquidity.check(api)?,
+        })
+    }
+}
+
+impl Default for LegMinOuts {
+    fn default() -> Self {
+        Self {
+            swap: AssetList::new(),
+            provide_liquidity: Uint128::zero(),
+            withdraw_liquidity: AssetList::new(),
+        }
+    }
+}
+
+impl LegMinOuts {
+    /// Returns the minimum amount to receive from a swap into `asset`, if any.
+    pub fn swap_min_out(&self, asset: &AssetInfo) -> Option<Uint128> {
+        self.swap.find(asset).map(|a| a.amount)
+    }
+}
+
+/// A vault to deposit into as part of a `BatchDeposit`
+#[cw_serde]
+pub struct BatchDepositEntry {
+    /// The address of the vault to deposit into
+    pub vault_address: String,
+    /// The fraction of the assets to deposit into this vault
+    pub weight: Decimal,
+    /// The minimum amount of vault tokens to receive from this vault
+    pub min_out: Uint128,
+}
 
 #[cw_serde]
 /// An enum to represent the different ways to receive assets when redeeming
@@ -264,4 +790,37 @@ pub enum ReceiveChoice {
     Underlying,
     /// Swap the base token to the specified asset
     SwapTo(AssetInfo),
+    /// Split the base token into a basket of assets. Each entry is an asset
+    /// to receive and the fraction of the base token's value to receive it
+    /// in. The fractions must be positive and sum to 1.
+    Basket(Vec<(AssetInfo, Decimal)>),
+}
+
+impl ReceiveChoice {
+    /// Checks that a `Basket` is non-empty, has no duplicate assets and that
+    /// its weights are positive and sum to 1.
+    pub fn check(&self) -> Result<(), ContractError> {
+        if let ReceiveChoice::Basket(basket) = self {
+            let invalid = |reason: &str| ContractError::InvalidBasket {
+                reason: reason.to_string(),
+            };
+            if basket.is_empty() {
+                return Err(invalid("basket is empty"));
+            }
+            let mut total_weight = Decimal::zero();
+            for (i, (info, weight)) in basket.iter().enumerate() {
+                if weight.is_zero() {
+                    return Err(invalid("weights must be positive"));
+                }
+                if basket[..i].iter().any(|(other, _)| other == info) {
+                    return Err(invalid("duplicate asset"));
+                }
+                total_weight += *weight;
+            }
+            if total_weight != Decimal::one() {
+                return Err(invalid("weights must sum to 1"));
+            }
+        }
+        Ok(())
+    }
 }
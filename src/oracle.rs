@@ -0,0 +1,154 @@
+use apollo_cw_asset::{AssetInfo, AssetInfoBase, AssetList};
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Addr, Decimal, Decimal256, Deps, StdError, StdResult, Uint128};
+use cw_address_like::AddressLike;
+
+use crate::state::ORACLE_CONFIG;
+use crate::ContractError;
+
+/// The price oracle configuration of the zapper. If set, the assets of swaps
+/// and zaps are valued at oracle prices, and those whose received value falls
+/// short of the given value by more than `max_deviation` are rejected.
+#[cw_serde]
+pub struct OracleConfig<T: AddressLike> {
+    /// The maximum fraction by which the oracle value of the assets received
+    /// may be less than the oracle value of the assets given.
+    pub max_deviation: Decimal,
+    /// The oracle contract to query the price of each asset from. Assets
+    /// without an oracle are not checked. All oracles must quote prices in the
+    /// same currency.
+    pub oracles: Vec<(AssetInfoBase<T>, T)>,
+}
+
+impl OracleConfig<String> {
+    /// Validates the oracle config and returns an `OracleConfig<Addr>`.
+    pub fn check(&self, deps: Deps) -> StdResult<OracleConfig<Addr>> {
+        if self.max_deviation >= Decimal::one() {
+            return Err(StdError::generic_err(
+                "Max deviation must be less than 100%",
+            ));
+        }
+
+        Ok(OracleConfig {
+            max_deviation: self.max_deviation,
+            oracles: self
+                .oracles
+                .iter()
+                .map(|(asset, oracle)| {
+                    Ok((asset.check(deps.api)?, deps.api.addr_validate(oracle)?))
+                })
+                .collect::<StdResult<Vec<_>>>()?,
+        })
+    }
+}
+
+impl OracleConfig<Addr> {
+    /// Queries the price of one unit of `asset` from its oracle. Returns `None`
+    /// if there is no oracle for the asset.
+    pub fn query_price(&self, deps: Deps, asset: &AssetInfo) -> StdResult<Option<Decimal>> {
+        let oracle = match self.oracles.iter().find(|(info, _)| info == asset) {
+            Some((_, oracle)) => oracle,
+            None => return Ok(None),
+        };
+        let res: PriceResponse = deps.querier.query_wasm_smart(
+            oracle,
+            &OracleQueryMsg::Price {
+                asset: asset.clone(),
+            },
+        )?;
+
+        Ok(Some(res.price))
+    }
+
+    /// Returns the total value of `assets` at oracle prices, or `None` if any
+    /// of them has no oracle.
+    pub fn query_value(&self, deps: Deps, assets: &AssetList) -> StdResult<Option<Decimal256>> {
+        let mut value = Decimal256::zero();
+        for asset in assets {
+            match self.query_price(deps, &asset.info)? {
+                Some(price) => {
+                    value += Decimal256::from_ratio(asset.amount, 1u128) * Decimal256::from(price)
+                }
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(value))
+    }
+}
+
+/// The query interface that oracle contracts, e.g. adapters of Pyth or Band
+/// price feeds, must implement.
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleQueryMsg {
+    /// Returns the price of one unit of `asset`
+    #[returns(PriceResponse)]
+    Price { asset: AssetInfo },
+}
+
+#[cw_serde]
+pub struct PriceResponse {
+    /// The price of one unit of the asset
+    pub price: Decimal,
+}
+
+/// Returns the minimum amount of `receive_asset` to receive when swapping
+/// `offer_assets`, which is the larger of `min_receive` and the amount implied
+/// by the oracle prices reduced by the max deviation. The oracle amount is
+/// only used if there is an oracle for all of the assets.
+pub fn swap_min_receive(
+    deps: Deps,
+    offer_assets: &AssetList,
+    receive_asset: &AssetInfo,
+    min_receive: Option<Uint128>,
+) -> Result<Option<Uint128>, ContractError> {
+    let config = match ORACLE_CONFIG.may_load(deps.storage)? {
+        Some(config) => config,
+        None => return Ok(min_receive),
+    };
+
+    let offer_value = config.query_value(deps, offer_assets)?;
+    let receive_price = config.query_price(deps, receive_asset)?;
+    let oracle_min_receive = match (offer_value, receive_price) {
+        (Some(offer_value), Some(receive_price)) if !receive_price.is_zero() => {
+            let min_value = offer_value * Decimal256::from(Decimal::one() - config.max_deviation);
+            let amount = (min_value / Decimal256::from(receive_price)).to_uint_floor();
+            Uint128::try_from(amount).map_err(StdError::from)?
+        }
+        _ => return Ok(min_receive),
+    };
+
+    Ok(Some(
+        min_receive.map_or(oracle_min_receive, |x| x.max(oracle_min_receive)),
+    ))
+}
+
+/// Errors if the oracle value of `received_assets` is less than the oracle
+/// value of `offer_assets` by more than the max deviation. Does nothing if
+/// `offer_assets` is empty, or if there is no oracle config or no oracle for
+/// any of the assets.
+pub fn check_oracle_rate(
+    deps: Deps,
+    offer_assets: &AssetList,
+    received_assets: &AssetList,
+) -> Result<(), ContractError> {
+    let config = match ORACLE_CONFIG.may_load(deps.storage)? {
+        Some(config) if offer_assets.len() > 0 => config,
+        _ => return Ok(()),
+    };
+
+    let offer_value = config.query_value(deps, offer_assets)?;
+    let received_value = config.query_value(deps, received_assets)?;
+    if let (Some(offer_value), Some(received_value)) = (offer_value, received_value) {
+        let min_value = offer_value * Decimal256::from(Decimal::one() - config.max_deviation);
+        if received_value < min_value {
+            return Err(ContractError::OracleRateDeviation {
+                min_value,
+                received_value,
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -1,12 +1,25 @@
 use std::collections::HashMap;
 
 use crate::msg::Pool;
-use apollo_cw_asset::AssetInfo;
-use cosmwasm_std::{Addr, Deps, Empty, Env, Order, StdError, StdResult};
+use apollo_cw_asset::{Asset, AssetInfo, AssetInfoUnchecked, AssetList};
+use cosmwasm_std::{Addr, Deps, Empty, Env, Order, StdError, StdResult, Uint128};
 use cw_storage_plus::Bound;
-
-use crate::msg::ReceiveChoice;
-use crate::state::{self, ASTROPORT_LIQUIDITY_MANAGER, DEFAULT_LIMIT, LOCKUP_IDS, ROUTER};
+use cw_utils::Expiration;
+
+use crate::fees::{Fees, Referral};
+use crate::helpers::{
+    get_inner_vault_redemption, get_nested_pools, get_vault_for_vault_token,
+    simulate_balancing_provide_liquidity, simulate_withdraw_nested_liquidity, split_by_weights,
+};
+use crate::msg::{
+    AnnotatedUnlockingPosition, AnnotatedUnlockingPositionsResponse, ConfigResponse, ReceiveChoice,
+    SimulateDepositResponse,
+};
+use crate::state::{
+    self, ASTROPORT_LIQUIDITY_MANAGER, DEFAULT_LIMIT, LIQUIDITY_HELPER, LOCKUP_IDS,
+    MAX_REFERRAL_FEE_BPS, MAX_SCANNED_UNLOCKING_POSITIONS, ROUTER,
+};
+use crate::ContractError;
 
 use cw_vault_standard::extensions::lockup::{LockupQueryMsg, UnlockingPosition};
 use cw_vault_standard::{ExtensionQueryMsg, VaultContract, VaultStandardQueryMsg};
@@ -26,6 +39,17 @@ pub fn query_depositable_assets(deps: Deps, vault_address: Addr) -> StdResult<Ve
     let pool =
         Pool::get_pool_for_lp_token(deps, &deposit_asset_info, astroport_liquidity_manager).ok();
 
+    // If the deposit asset is the vault token of another vault, anything that
+    // can be deposited into that vault can be deposited
+    if pool.is_none() {
+        if let Some(inner_vault) = get_vault_for_vault_token(deps, &deposit_asset_info) {
+            if inner_vault.addr != vault_address {
+                let inner_depositable_assets = query_depositable_assets(deps, inner_vault.addr)?;
+                return Ok([vec![deposit_asset_info], inner_depositable_assets].concat());
+            }
+        }
+    }
+
     // If deposit asset is an LP token, the target of the basket liquidation is
     // the first asset in the pool. Otherwise it is just the deposit asset.
     let target_asset = match pool {
@@ -123,6 +147,20 @@ pub fn query_receive_choices(deps: Deps, vault_address: Addr) -> StdResult<Vec<R
     Ok(receive_choices)
 }
 
+/// Queries the unlocking position `lockup_id` from the vault.
+pub fn query_unlocking_position(
+    deps: Deps,
+    vault_address: &Addr,
+    lockup_id: u64,
+) -> StdResult<UnlockingPosition> {
+    deps.querier.query_wasm_smart(
+        vault_address,
+        &VaultStandardQueryMsg::<ExtensionQueryMsg>::VaultExtension(ExtensionQueryMsg::Lockup(
+            LockupQueryMsg::UnlockingPosition { lockup_id },
+        )),
+    )
+}
+
 pub fn query_user_unlocking_positions_for_vault(
     deps: Deps,
     env: Env,
@@ -144,12 +182,7 @@ pub fn query_user_unlocking_positions_for_vault(
     for res in user_lockup_ids {
         let (lockup_id, _) = res?;
 
-        let unlocking_position = deps.querier.query_wasm_smart::<UnlockingPosition>(
-            &vault_address,
-            &VaultStandardQueryMsg::<ExtensionQueryMsg>::VaultExtension(ExtensionQueryMsg::Lockup(
-                LockupQueryMsg::UnlockingPosition { lockup_id },
-            )),
-        )?;
+        let unlocking_position = query_unlocking_position(deps, &vault_address, lockup_id)?;
 
         if unlocking_position.owner == env.contract.address {
             unlocking_positions.push(unlocking_position);
@@ -179,12 +212,7 @@ pub fn query_all_user_unlocking_positions(
     for item in user_lockup_ids {
         let ((vault_address, lockup_id), _) = item?;
 
-        let unlocking_position = deps.querier.query_wasm_smart::<UnlockingPosition>(
-            &vault_address,
-            &VaultStandardQueryMsg::<ExtensionQueryMsg>::VaultExtension(ExtensionQueryMsg::Lockup(
-                LockupQueryMsg::UnlockingPosition { lockup_id },
-            )),
-        )?;
+        let unlocking_position = query_unlocking_position(deps, &vault_address, lockup_id)?;
 
         if unlocking_position.owner == env.contract.address {
             if let Some(positions) = unlocking_positions_per_vault.get_mut(&vault_address) {
@@ -197,3 +225,429 @@ pub fn query_all_user_unlocking_positions(
 
     Ok(unlocking_positions_per_vault)
 }
+
+pub fn query_annotated_user_unlocking_positions(
+    deps: Deps,
+    env: Env,
+    user: Addr,
+    start_after_vault_addr: Option<String>,
+    start_after_id: Option<u64>,
+    limit: Option<u32>,
+    claimable_only: bool,
+) -> StdResult<AnnotatedUnlockingPositionsResponse> {
+    // Keep scanning until `limit` positions are found, so that positions
+    // filtered out by `claimable_only` do not count towards the limit, but
+    // scan at most MAX_SCANNED_UNLOCKING_POSITIONS positions
+    let limit = limit.unwrap_or(DEFAULT_LIMIT) as usize;
+    let user_lockup_ids = state::paginate_all_user_unlocking_positions(
+        deps,
+        user,
+        start_after_vault_addr,
+        start_after_id,
+        Some(MAX_SCANNED_UNLOCKING_POSITIONS),
+    )?;
+
+    // The base token, receive choice and fees of each vault, which are the
+    // same for all of its positions
+    let astroport_liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?;
+    let mut vaults: HashMap<Addr, (AssetInfo, ReceiveChoice, Fees)> = HashMap::new();
+
+    let mut positions = vec![];
+    let mut last_scanned = None;
+    let mut scanned = 0;
+    for item in user_lockup_ids {
+        if positions.len() >= limit {
+            break;
+        }
+        let ((vault_address, lockup_id), _) = item?;
+        scanned += 1;
+        last_scanned = Some((vault_address.clone(), lockup_id));
+
+        let position = query_unlocking_position(deps, &vault_address, lockup_id)?;
+        if position.owner != env.contract.address {
+            continue;
+        }
+        let is_claimable = position.release_at.is_expired(&env.block);
+        if claimable_only && !is_claimable {
+            continue;
+        }
+        let (seconds_remaining, blocks_remaining) = match position.release_at {
+            Expiration::AtTime(time) => (
+                Some(time.seconds().saturating_sub(env.block.time.seconds())),
+                None,
+            ),
+            Expiration::AtHeight(height) => (None, Some(height.saturating_sub(env.block.height))),
+            Expiration::Never {} => (None, None),
+        };
+
+        // Estimate the value as received when withdrawing as the underlying
+        // assets, or as the base token if it does not wrap other assets
+        if !vaults.contains_key(&vault_address) {
+            let vault: VaultContract<Empty, Empty> =
+                VaultContract::new(&deps.querier, &vault_address)?;
+            let base_token_info = match deps.api.addr_validate(&vault.base_token) {
+                Ok(addr) => AssetInfo::cw20(addr),
+                Err(_) => AssetInfo::native(&vault.base_token),
+            };
+            let receive_choice =
+                if !get_nested_pools(deps, &base_token_info, astroport_liquidity_manager.clone())?
+                    .is_empty()
+                    || get_vault_for_vault_token(deps, &base_token_info).is_some()
+                {
+                    ReceiveChoice::Underlying
+                } else {
+                    ReceiveChoice::BaseToken
+                };
+            let fees = Fees::resolve(deps, Some(&vault_address), None)
+                .map_err(|e| StdError::generic_err(e.to_string()))?;
+            vaults.insert(
+                vault_address.clone(),
+                (base_token_info, receive_choice, fees),
+            );
+        }
+        let (base_token_info, receive_choice, fees) = &vaults[&vault_address];
+        let estimated_underlying_assets = simulate_zap_base_tokens(
+            deps,
+            Asset::new(base_token_info.clone(), position.base_token_amount),
+            receive_choice.clone(),
+            fees,
+        )?;
+
+        positions.push(AnnotatedUnlockingPosition {
+            vault_address,
+            position,
+            is_claimable,
+            seconds_remaining,
+            blocks_remaining,
+            estimated_underlying_assets,
+        });
+    }
+
+    // All of the user's positions have been scanned if neither the limit nor
+    // the maximum number of scanned positions was reached
+    let start_after = if positions.len() < limit && scanned < MAX_SCANNED_UNLOCKING_POSITIONS {
+        None
+    } else {
+        last_scanned
+    };
+
+    Ok(AnnotatedUnlockingPositionsResponse {
+        positions,
+        start_after,
+    })
+}
+
+pub fn query_simulate_deposit(
+    deps: Deps,
+    env: Env,
+    assets: AssetList,
+    vault_address: Addr,
+    fees: &Fees,
+) -> StdResult<SimulateDepositResponse> {
+    // Deduct the fees from the deposited assets
+    let (_, assets, _) = fees.take_from_assets(&assets)?;
+
+    // Query the vault info to get the deposit asset
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
+    let deposit_asset_info = match deps.api.addr_validate(&vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&vault.base_token),
+    };
+
+    // If the assets are already the deposit asset, just convert to vault tokens.
+    // We use ConvertToShares rather than PreviewDeposit as not all vaults (e.g.
+    // locked-astroport-vault) implement the latter.
+    if assets.len() == 1 && assets.to_vec()[0].info == deposit_asset_info {
+        let base_token_amount = assets.to_vec()[0].amount;
+        return Ok(SimulateDepositResponse {
+            vault_token_amount: vault.query_convert_to_shares(&deps.querier, base_token_amount)?,
+            base_token_amount,
+            liquidated_assets: assets,
+        });
+    }
+
+    // Check if the depositable asset is an LP token, possibly of a pool that
+    // contains other LP tokens
+    let astroport_liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?;
+    let pools = get_nested_pools(deps, &deposit_asset_info, astroport_liquidity_manager)?;
+
+    // If the deposit asset is the vault token of another vault, simulate the
+    // deposit into that vault first, as in execute_deposit
+    if pools.is_empty() {
+        if let Some(inner_vault) = get_vault_for_vault_token(deps, &deposit_asset_info) {
+            if inner_vault.addr != vault_address {
+                let inner =
+                    query_simulate_deposit(deps, env, assets, inner_vault.addr, &Fees::default())?;
+                return Ok(SimulateDepositResponse {
+                    vault_token_amount: vault
+                        .query_convert_to_shares(&deps.querier, inner.vault_token_amount)?,
+                    base_token_amount: inner.vault_token_amount,
+                    liquidated_assets: inner.liquidated_assets,
+                });
+            }
+        }
+    }
+
+    // Same basket liquidation target as in execute_deposit
+    let (receive_asset_infos, receive_asset_info) = match pools.last() {
+        Some(innermost_pool) => {
+            let mut receive_asset_infos: Vec<AssetInfo> = vec![];
+            for pool in &pools {
+                for info in pool.pool_assets(deps)? {
+                    if !receive_asset_infos.contains(&info) {
+                        receive_asset_infos.push(info);
+                    }
+                }
+            }
+            let receive_asset_info = innermost_pool.pool_assets(deps)?[0].clone();
+            (receive_asset_infos, receive_asset_info)
+        }
+        None => (vec![deposit_asset_info.clone()], deposit_asset_info.clone()),
+    };
+
+    // Assets that are already one of the targets are kept as is, the rest are
+    // basket liquidated into the target
+    let mut liquidated_assets = AssetList::new();
+    let mut liquidate_assets = AssetList::new();
+    for asset in assets.iter() {
+        if receive_asset_infos.contains(&asset.info) {
+            liquidated_assets.add(asset)?;
+        } else {
+            liquidate_assets.add(asset)?;
+        }
+    }
+    if liquidate_assets.len() > 0 {
+        let router = ROUTER.load(deps.storage)?;
+        let amount = router.simulate_basket_liquidate(
+            &deps.querier,
+            liquidate_assets,
+            &receive_asset_info,
+        )?;
+        liquidated_assets.add(&Asset::new(receive_asset_info, amount))?;
+    }
+
+    // Estimate the amount of base tokens deposited into the vault by providing
+    // liquidity to the pools from the innermost outwards, as in execute_deposit
+    let base_token_amount = if pools.is_empty() {
+        liquidated_assets
+            .find(&deposit_asset_info)
+            .map(|x| x.amount)
+            .unwrap_or_default()
+    } else {
+        let mut balances = liquidated_assets.clone();
+        let mut lp_amount = Uint128::zero();
+        for (i, pool) in pools.iter().enumerate().rev() {
+            let pool_assets: AssetList = pool
+                .pool_assets(deps)?
+                .into_iter()
+                .filter_map(|info| balances.find(&info).cloned())
+                .collect::<Vec<_>>()
+                .into();
+            // Nothing is provided to an inner pool without balances of its
+            // assets
+            if i > 0 && pool_assets.iter().all(|a| a.amount.is_zero()) {
+                continue;
+            }
+            lp_amount = simulate_balancing_provide_liquidity(deps, &env, pool, &pool_assets)?;
+            balances.deduct_many(&pool_assets)?;
+            balances.add(&Asset::new(pool.lp_token(), lp_amount))?;
+        }
+        lp_amount
+    };
+
+    Ok(SimulateDepositResponse {
+        vault_token_amount: vault.query_convert_to_shares(&deps.querier, base_token_amount)?,
+        base_token_amount,
+        liquidated_assets,
+    })
+}
+
+pub fn query_simulate_redeem(
+    deps: Deps,
+    vault_address: Addr,
+    vault_token_amount: Uint128,
+    receive_choice: ReceiveChoice,
+    referral: Option<Referral>,
+) -> StdResult<AssetList> {
+    // Query the vault info to get the base token
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
+    let vault_base_token = match deps.api.addr_validate(&vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&vault.base_token),
+    };
+
+    // Same as for deposits, we use ConvertToAssets rather than PreviewRedeem
+    let base_token_amount = vault.query_convert_to_assets(&deps.querier, vault_token_amount)?;
+
+    let fees = Fees::resolve(deps, Some(&vault_address), referral.as_ref())
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    simulate_zap_base_tokens(
+        deps,
+        Asset::new(vault_base_token, base_token_amount),
+        receive_choice,
+        &fees,
+    )
+}
+
+/// Estimates the assets received when zapping `base_token` according to
+/// `receive_choice`, after deducting `fees`. Mirrors `callback_after_redeem`
+/// and `callback_after_withdraw_liq`.
+pub fn simulate_zap_base_tokens(
+    deps: Deps,
+    base_token: Asset,
+    receive_choice: ReceiveChoice,
+    fees: &Fees,
+) -> StdResult<AssetList> {
+    let astroport_liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?;
+    let pools = get_nested_pools(deps, &base_token.info, astroport_liquidity_manager.clone())?;
+
+    // Same as in callback_after_redeem, vault tokens of an inner vault are
+    // redeemed from that vault first
+    if pools.is_empty() {
+        if let Some((inner_vault, inner_base_token, inner_receive_choice)) =
+            get_inner_vault_redemption(
+                deps,
+                &base_token.info,
+                &receive_choice,
+                astroport_liquidity_manager,
+            )
+            .map_err(|e| StdError::generic_err(e.to_string()))?
+        {
+            let amount = inner_vault.query_convert_to_assets(&deps.querier, base_token.amount)?;
+            return simulate_zap_base_tokens(
+                deps,
+                Asset::new(inner_base_token, amount),
+                inner_receive_choice,
+                fees,
+            );
+        }
+    }
+
+    // For the estimate we take the fees from the base token up front, which is
+    // close enough to taking them from the withdrawn liquidity
+    let (_, base_tokens, _) = fees.take_from_assets(&vec![base_token.clone()].into())?;
+    let base_token = Asset::new(
+        base_token.info.clone(),
+        base_tokens
+            .find(&base_token.info)
+            .map(|x| x.amount)
+            .unwrap_or_default(),
+    );
+
+    receive_choice
+        .check()
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    match receive_choice {
+        ReceiveChoice::BaseToken => Ok(vec![base_token].into()),
+        ReceiveChoice::SwapTo(requested_asset) => {
+            if requested_asset == base_token.info {
+                return Ok(vec![base_token].into());
+            }
+
+            let router = ROUTER.load(deps.storage)?;
+            let amount = if pools.is_empty() {
+                router.simulate_basket_liquidate(
+                    &deps.querier,
+                    vec![base_token].into(),
+                    &requested_asset,
+                )?
+            } else {
+                let withdrawn = simulate_withdraw_nested_liquidity(
+                    deps,
+                    &pools,
+                    &base_token,
+                    std::slice::from_ref(&requested_asset),
+                )?;
+
+                // The requested asset is sent as is, the rest is basket liquidated
+                let requested_asset_amount = withdrawn
+                    .find(&requested_asset)
+                    .map(|x| x.amount)
+                    .unwrap_or_default();
+                let offer_assets: AssetList = withdrawn
+                    .into_iter()
+                    .filter(|x| x.info != requested_asset && !x.amount.is_zero())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .into();
+
+                if offer_assets.len() == 0 {
+                    requested_asset_amount
+                } else {
+                    requested_asset_amount
+                        + router.simulate_basket_liquidate(
+                            &deps.querier,
+                            offer_assets,
+                            &requested_asset,
+                        )?
+                }
+            };
+
+            Ok(vec![Asset::new(requested_asset, amount)].into())
+        }
+        ReceiveChoice::Underlying => {
+            if pools.is_empty() {
+                return Err(StdError::generic_err(
+                    ContractError::UnsupportedWithdrawal {}.to_string(),
+                ));
+            }
+            simulate_withdraw_nested_liquidity(deps, &pools, &base_token, &[])
+        }
+        ReceiveChoice::Basket(basket) => {
+            // Same as in callback_after_redeem, liquidity is withdrawn first if
+            // the base token is an LP token
+            let assets = if pools.is_empty() {
+                vec![base_token].into()
+            } else {
+                let keep: Vec<AssetInfo> = basket.iter().map(|(info, _)| info.clone()).collect();
+                simulate_withdraw_nested_liquidity(deps, &pools, &base_token, &keep)?
+            };
+
+            let router = ROUTER.load(deps.storage)?;
+            let mut received = AssetList::new();
+            for (target, part) in split_by_weights(&assets, &basket)? {
+                let mut amount = part.find(&target).map(|x| x.amount).unwrap_or_default();
+                let offer_assets: AssetList = part
+                    .iter()
+                    .filter(|a| a.info != target)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .into();
+                if offer_assets.len() > 0 {
+                    amount +=
+                        router.simulate_basket_liquidate(&deps.querier, offer_assets, &target)?;
+                }
+                received.add(&Asset::new(target, amount))?;
+            }
+
+            Ok(received)
+        }
+    }
+}
+
+/// Returns the contract's balances of `asset_infos`.
+pub fn query_contract_balances(
+    deps: Deps,
+    env: Env,
+    asset_infos: Vec<AssetInfoUnchecked>,
+) -> StdResult<AssetList> {
+    let asset_infos = asset_infos
+        .iter()
+        .map(|info| info.check(deps.api))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    AssetList::query_asset_info_balances(asset_infos, &deps.querier, &env.contract.address)
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    Ok(ConfigResponse {
+        router: ROUTER.load(deps.storage)?,
+        liquidity_helper: LIQUIDITY_HELPER.load(deps.storage)?,
+        astroport_liquidity_manager: ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?,
+        max_referral_fee_bps: MAX_REFERRAL_FEE_BPS
+            .may_load(deps.storage)?
+            .unwrap_or_default(),
+    })
+}
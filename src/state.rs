@@ -1,24 +1,51 @@
 use std::iter::Take;
 
+use apollo_cw_asset::AssetList;
 use cosmwasm_std::{Addr, Deps, Order, StdError, StdResult};
 use cw_dex_router::helpers::CwDexRouter;
 use cw_storage_plus::{Bound, Item, Map};
 use liquidity_helper::LiquidityHelper;
 
+use crate::fees::FeeConfig;
+use crate::oracle::OracleConfig;
+
 pub const ROUTER: Item<CwDexRouter> = Item::new("router");
 pub const LIQUIDITY_HELPER: Item<LiquidityHelper> = Item::new("liquidity_helper");
 
 pub const ASTROPORT_LIQUIDITY_MANAGER: Item<Addr> = Item::new("astroport_liquidity_manager");
 
+/// The protocol fee configuration. No fees are taken if this is not set.
+pub const FEE_CONFIG: Item<FeeConfig<Addr>> = Item::new("fee_config");
+
+/// The maximum referral fee in basis points. Referrals are not allowed to take
+/// a fee if this is not set.
+pub const MAX_REFERRAL_FEE_BPS: Item<u16> = Item::new("max_referral_fee_bps");
+
+/// The price oracle configuration. Zaps are not checked against oracle prices
+/// if this is not set.
+pub const ORACLE_CONFIG: Item<OracleConfig<Addr>> = Item::new("oracle_config");
+
 /// Stores the lockup ids for unlocking positions. The key is a tuple of
 /// (owner_address, vault_address, lockup_id).
 pub const LOCKUP_IDS: Map<(Addr, Addr, u64), ()> = Map::new("lockup_ids");
 
+/// Balances held by the contract that do not belong to the operation in
+/// progress: the balances the contract held before the operation started and
+/// the assets of the remaining deposits of a `BatchDeposit`. Callbacks that use
+/// the contract's balances must leave these untouched. Only set while an
+/// operation is in progress, and only one operation can be in progress at a
+/// time.
+pub const RESERVED_BALANCES: Item<AssetList> = Item::new("reserved_balances");
+
 pub const TEMP_LOCK_KEY: Item<(Addr, Addr)> = Item::new("temp_lock_key");
 
 /// The default limit when paginating and no limit is specified
 pub const DEFAULT_LIMIT: u32 = 10;
 
+/// The maximum number of unlocking positions scanned by a single
+/// AnnotatedUserUnlockingPositions query
+pub const MAX_SCANNED_UNLOCKING_POSITIONS: u32 = 50;
+
 pub type LockupIdIterator<'a> =
     Take<Box<dyn Iterator<Item = Result<((Addr, u64), ()), StdError>> + 'a>>;
 
@@ -0,0 +1,376 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use cosmwasm_std::{
+    to_json_binary, Addr, CosmosMsg, DepsMut, Empty, Env, Event, MessageInfo, Response, Uint128,
+};
+use cw_vault_standard::VaultContract;
+
+use crate::deposit::deposit;
+use crate::helpers::{
+    get_inner_vault_redemption, get_nested_pools, query_unreserved_balances, reserve_balances,
+    VaultHelper,
+};
+use crate::msg::{CallbackMsg, LegMinOuts, Pool, ReceiveChoice};
+use crate::state::ASTROPORT_LIQUIDITY_MANAGER;
+use crate::withdraw::{take_unlocking_position, withdraw_touched_assets};
+use crate::ContractError;
+
+pub fn execute_migrate_between_vaults(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    from_vault: Addr,
+    to_vault: Addr,
+    recipient: Option<String>,
+    min_out: Uint128,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    // Make sure exactly one native token was sent. That it is the vault token is
+    // checked in `migrate_between_vaults`.
+    if info.funds.len() != 1 {
+        return Err(ContractError::InvalidVaultToken {});
+    }
+    let vault_tokens = Asset::native(&info.funds[0].denom, info.funds[0].amount);
+
+    migrate_between_vaults(
+        deps,
+        env,
+        info.sender,
+        from_vault,
+        vault_tokens,
+        to_vault,
+        recipient,
+        min_out,
+        leg_min_outs,
+    )
+}
+
+/// Redeems `vault_tokens`, which must already be held by the contract, from
+/// `from_vault` and deposits the redeemed base tokens into `to_vault`. Called
+/// by `execute_migrate_between_vaults` for native vault tokens and by the CW20
+/// receive hook for CW20 vault tokens.
+pub fn migrate_between_vaults(
+    mut deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    from_vault: Addr,
+    vault_tokens: Asset,
+    to_vault: Addr,
+    recipient: Option<String>,
+    min_out: Uint128,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    if from_vault == to_vault {
+        return Err(ContractError::Generic(
+            "Can not migrate to the same vault".to_string(),
+        ));
+    }
+
+    // The sent vault tokens are already held by the contract and must not be
+    // reserved, e.g. if they are the base token of `to_vault`
+    let return_leftovers_msg = reserve_migration_balances(
+        deps.branch(),
+        &env,
+        &from_vault,
+        &to_vault,
+        &vec![vault_tokens.clone()].into(),
+        &sender,
+    )?;
+
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(sender), |x| deps.api.addr_validate(&x))?;
+
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &from_vault)?;
+
+    // Make sure the vault token was sent
+    vault.check_vault_tokens(deps.api, &vault_tokens)?;
+    let redeem_msg = vault.redeem_vault_tokens(&vault_tokens)?;
+
+    let base_token = match deps.api.addr_validate(&vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&vault.base_token),
+    };
+
+    let event = Event::new("apollo/vault-zapper/migrate_between_vaults")
+        .add_attribute("from_vault", &from_vault)
+        .add_attribute("to_vault", &to_vault)
+        .add_attribute("vault_tokens", to_json_binary(&vault_tokens)?.to_string())
+        .add_attribute("recipient", &recipient)
+        .add_attribute("min_out", min_out)
+        .add_attribute("leg_min_outs", to_json_binary(&leg_min_outs)?.to_string());
+
+    Ok(Response::new()
+        .add_message(redeem_msg)
+        .add_message(
+            CallbackMsg::ZapIntoVault {
+                base_token,
+                vault_address: to_vault,
+                recipient,
+                min_out,
+                leg_min_outs,
+            }
+            .into_cosmos_msg(&env)?,
+        )
+        .add_message(return_leftovers_msg)
+        .add_event(event))
+}
+
+/// Withdraws the matured unlocking position `lockup_id` from `from_vault` and
+/// deposits the withdrawn base tokens into `to_vault`.
+pub fn execute_withdraw_unlocked_to_vault(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    from_vault: Addr,
+    lockup_id: u64,
+    to_vault: Addr,
+    recipient: Option<String>,
+    min_out: Uint128,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    if from_vault == to_vault {
+        return Err(ContractError::Generic(
+            "Can not migrate to the same vault".to_string(),
+        ));
+    }
+
+    let withdraw_unlocked_msg =
+        take_unlocking_position(deps.branch(), &info.sender, &from_vault, lockup_id)?;
+    let return_leftovers_msg = reserve_migration_balances(
+        deps.branch(),
+        &env,
+        &from_vault,
+        &to_vault,
+        &AssetList::new(),
+        &info.sender,
+    )?;
+
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(info.sender), |x| deps.api.addr_validate(&x))?;
+
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &from_vault)?;
+    let base_token = match deps.api.addr_validate(&vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&vault.base_token),
+    };
+
+    let event = Event::new("apollo/vault-zapper/withdraw_unlocked_to_vault")
+        .add_attribute("from_vault", &from_vault)
+        .add_attribute("lockup_id", lockup_id.to_string())
+        .add_attribute("to_vault", &to_vault)
+        .add_attribute("recipient", &recipient)
+        .add_attribute("min_out", min_out)
+        .add_attribute("leg_min_outs", to_json_binary(&leg_min_outs)?.to_string());
+
+    Ok(Response::new()
+        .add_message(withdraw_unlocked_msg)
+        .add_message(
+            CallbackMsg::ZapIntoVault {
+                base_token,
+                vault_address: to_vault,
+                recipient,
+                min_out,
+                leg_min_outs,
+            }
+            .into_cosmos_msg(&env)?,
+        )
+        .add_message(return_leftovers_msg)
+        .add_event(event))
+}
+
+/// Reserves the contract's balances, excluding `held`, of the assets touched
+/// by withdrawing from `from_vault` and depositing into `to_vault` and returns
+/// the `ReturnLeftovers` message that ends the migration.
+fn reserve_migration_balances(
+    deps: DepsMut,
+    env: &Env,
+    from_vault: &Addr,
+    to_vault: &Addr,
+    held: &AssetList,
+    sender: &Addr,
+) -> Result<CosmosMsg, ContractError> {
+    // The assets withdrawn from `from_vault` are deposited into `to_vault`,
+    // which touches its base token and the assets it is made of
+    let touched_assets =
+        withdraw_touched_assets(deps.as_ref(), &[from_vault.clone(), to_vault.clone()])?;
+
+    reserve_balances(deps, env, touched_assets, held, sender)
+}
+
+/// Deposits the contract's unreserved balance of `base_token` into the vault. If
+/// `base_token` is not the vault's own base token, it is unwrapped first: if it
+/// is an LP token, liquidity is withdrawn from its pool and any nested pools
+/// and the withdrawn assets are deposited in `callback_deposit_balances`, and
+/// if it is the vault token of another vault, it is redeemed from that vault
+/// and the redeemed base tokens are zapped in another `ZapIntoVault` callback.
+pub fn callback_zap_into_vault(
+    deps: DepsMut,
+    env: Env,
+    base_token: AssetInfo,
+    vault_address: Addr,
+    recipient: Addr,
+    min_out: Uint128,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    let base_token = query_unreserved_balances(deps.as_ref(), &env, vec![base_token.clone()])?
+        .find(&base_token)
+        .cloned()
+        .unwrap_or_else(|| Asset::new(base_token, Uint128::zero()));
+
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
+    let vault_base_token = match deps.api.addr_validate(&vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&vault.base_token),
+    };
+    let astroport_liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?;
+    let mut pools = get_nested_pools(
+        deps.as_ref(),
+        &base_token.info,
+        astroport_liquidity_manager.clone(),
+    )?
+    .into_iter();
+    let pool = pools.next();
+    let inner_pools: Vec<Pool> = pools.collect();
+
+    let event = Event::new("apollo/vault-zapper/callback_zap_into_vault")
+        .add_attribute("base_token", to_json_binary(&base_token)?.to_string())
+        .add_attribute("vault_address", &vault_address);
+
+    if base_token.info != vault_base_token {
+        if let Some(pool) = pool {
+            let pool_assets = pool.pool_assets(deps.as_ref())?;
+            let res = pool.withdraw_liquidity(
+                deps.as_ref(),
+                &env,
+                base_token,
+                leg_min_outs.withdraw_liquidity.clone(),
+            )?;
+            return Ok(res
+                .add_message(
+                    CallbackMsg::DepositBalances {
+                        assets: pool_assets,
+                        vault_address,
+                        recipient,
+                        min_out,
+                        inner_pools,
+                        leg_min_outs: LegMinOuts {
+                            withdraw_liquidity: AssetList::new(),
+                            ..leg_min_outs
+                        },
+                    }
+                    .into_cosmos_msg(&env)?,
+                )
+                .add_event(event));
+        }
+
+        if let Some((inner_vault, inner_base_token, _)) = get_inner_vault_redemption(
+            deps.as_ref(),
+            &base_token.info,
+            &ReceiveChoice::Underlying,
+            astroport_liquidity_manager,
+        )? {
+            let event = event.add_attribute("inner_vault", &inner_vault.addr);
+            return Ok(Response::new()
+                .add_message(inner_vault.redeem_vault_tokens(&base_token)?)
+                .add_message(
+                    CallbackMsg::ZapIntoVault {
+                        base_token: inner_base_token,
+                        vault_address,
+                        recipient,
+                        min_out,
+                        leg_min_outs,
+                    }
+                    .into_cosmos_msg(&env)?,
+                )
+                .add_event(event));
+        }
+    }
+
+    let res = deposit(
+        deps,
+        env,
+        recipient.clone(),
+        vec![base_token].into(),
+        vault_address,
+        Some(recipient.to_string()),
+        min_out,
+        None,
+        None,
+        leg_min_outs,
+    )?;
+    Ok(res.add_event(event))
+}
+
+/// Deposits the contract's unreserved balances of `assets` into the vault. If
+/// one of the assets is the LP token of the first of `inner_pools` and not the
+/// vault's base token, liquidity is withdrawn from that pool first and the
+/// withdrawn assets are deposited in another `DepositBalances` callback.
+pub fn callback_deposit_balances(
+    deps: DepsMut,
+    env: Env,
+    assets: Vec<AssetInfo>,
+    vault_address: Addr,
+    recipient: Addr,
+    min_out: Uint128,
+    inner_pools: Vec<Pool>,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    let balances = query_unreserved_balances(deps.as_ref(), &env, assets.clone())?;
+
+    if let Some((pool, inner_pools)) = inner_pools.split_first() {
+        let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
+        let vault_base_token = match deps.api.addr_validate(&vault.base_token) {
+            Ok(addr) => AssetInfo::cw20(addr),
+            Err(_) => AssetInfo::native(&vault.base_token),
+        };
+        let lp_token = pool.lp_token();
+        if let Some(lp_token) = balances.find(&lp_token) {
+            if !lp_token.amount.is_zero() && lp_token.info != vault_base_token {
+                let mut assets: Vec<AssetInfo> =
+                    assets.into_iter().filter(|a| a != &lp_token.info).collect();
+                for info in pool.pool_assets(deps.as_ref())? {
+                    if !assets.contains(&info) {
+                        assets.push(info);
+                    }
+                }
+
+                let res = pool.withdraw_liquidity(
+                    deps.as_ref(),
+                    &env,
+                    lp_token.clone(),
+                    AssetList::new(),
+                )?;
+                return Ok(res.add_message(
+                    CallbackMsg::DepositBalances {
+                        assets,
+                        vault_address,
+                        recipient,
+                        min_out,
+                        inner_pools: inner_pools.to_vec(),
+                        leg_min_outs,
+                    }
+                    .into_cosmos_msg(&env)?,
+                ));
+            }
+        }
+    }
+
+    let assets: AssetList = balances
+        .into_iter()
+        .filter(|a| !a.amount.is_zero())
+        .cloned()
+        .collect::<Vec<_>>()
+        .into();
+
+    deposit(
+        deps,
+        env,
+        recipient.clone(),
+        assets,
+        vault_address,
+        Some(recipient.to_string()),
+        min_out,
+        None,
+        None,
+        leg_min_outs,
+    )
+}
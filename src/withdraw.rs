@@ -1,17 +1,30 @@
 use crate::msg::Pool;
 use apollo_cw_asset::{Asset, AssetInfo, AssetList};
 use apollo_utils::assets::receive_assets;
+use apollo_utils::responses::merge_responses;
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{
-    to_json_binary, Addr, CosmosMsg, DepsMut, Empty, Env, Event, MessageInfo, Response, Uint128,
-    WasmMsg,
+    to_json_binary, Addr, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env, Event, MessageInfo, Order,
+    Response, StdResult, Uint128, WasmMsg,
 };
+use cw_dex_router::helpers::CwDexRouter;
+use cw_storage_plus::Bound;
 use cw_vault_standard::extensions::lockup::LockupExecuteMsg;
 use cw_vault_standard::msg::{ExtensionExecuteMsg, VaultStandardExecuteMsg as VaultExecuteMsg};
 use cw_vault_standard::VaultContract;
 
-use crate::msg::{CallbackMsg, ReceiveChoice};
-use crate::state::{ASTROPORT_LIQUIDITY_MANAGER, LOCKUP_IDS, ROUTER};
+use crate::fees::{Fees, Referral};
+use crate::helpers::{
+    base_token_assets, get_inner_vault_redemption, get_nested_pools, query_unreserved_balances,
+    reserve_balances, split_by_weights, VaultHelper,
+};
+use crate::msg::{CallbackMsg, LegMinOuts, ReceiveChoice};
+use crate::oracle::swap_min_receive;
+use crate::query::query_unlocking_position;
+use crate::state::{
+    paginate_all_user_unlocking_positions, ASTROPORT_LIQUIDITY_MANAGER, DEFAULT_LIMIT, LOCKUP_IDS,
+    ORACLE_CONFIG, ROUTER,
+};
 use crate::ContractError;
 
 #[cw_serde]
@@ -21,28 +34,213 @@ pub enum RedeemType {
 }
 
 pub fn execute_redeem(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     vault_address: Addr,
     recipient: Option<String>,
     receive_choice: ReceiveChoice,
     min_out: AssetList,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
 ) -> Result<Response, ContractError> {
-    withdraw(
+    // Make sure exactly one native token was sent. That it is the vault token is
+    // checked in `redeem`.
+    if info.funds.len() != 1 {
+        return Err(ContractError::InvalidVaultToken {});
+    }
+    let vault_tokens = Asset::native(&info.funds[0].denom, info.funds[0].amount);
+
+    let touched_assets =
+        withdraw_touched_assets(deps.as_ref(), std::slice::from_ref(&vault_address))?;
+    let return_leftovers_msg = reserve_balances(
+        deps.branch(),
+        &env,
+        touched_assets,
+        &AssetList::new(),
+        &info.sender,
+    )?;
+
+    let res = redeem(
         deps,
         env,
-        info,
+        info.sender,
         vault_address,
+        vault_tokens,
+        recipient,
+        receive_choice,
+        Some(min_out),
+        referral,
+        leg_min_outs,
+    )?;
+
+    Ok(res.add_message(return_leftovers_msg))
+}
+
+/// Returns the assets that withdrawing from the vaults can leave behind in the
+/// contract: the base token of each vault and the assets it is made of.
+pub fn withdraw_touched_assets(
+    deps: Deps,
+    vault_addresses: &[Addr],
+) -> Result<Vec<AssetInfo>, ContractError> {
+    let mut touched_assets: Vec<AssetInfo> = vec![];
+    for vault_address in vault_addresses {
+        let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, vault_address)?;
+        let base_token = match deps.api.addr_validate(&vault.base_token) {
+            Ok(addr) => AssetInfo::cw20(addr),
+            Err(_) => AssetInfo::native(&vault.base_token),
+        };
+        for info in base_token_assets(deps, &base_token)? {
+            if !touched_assets.contains(&info) {
+                touched_assets.push(info);
+            }
+        }
+    }
+
+    Ok(touched_assets)
+}
+
+/// Redeems `vault_tokens`, which must already be held by the contract, from
+/// the vault. Called by `execute_redeem` for native vault tokens and by the
+/// CW20 receive hook for CW20 vault tokens. `min_out` is `None` for the
+/// redemptions of a batch.
+pub fn redeem(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    vault_address: Addr,
+    vault_tokens: Asset,
+    recipient: Option<String>,
+    receive_choice: ReceiveChoice,
+    min_out: Option<AssetList>,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
+
+    // Make sure the vault token was sent
+    vault.check_vault_tokens(deps.api, &vault_tokens)?;
+    let redeem_msg = vault.redeem_vault_tokens(&vault_tokens)?;
+
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(sender), |x| deps.api.addr_validate(&x))?;
+
+    withdraw(
+        deps,
+        env,
+        vault,
+        redeem_msg,
         recipient,
         receive_choice,
         min_out,
         RedeemType::Normal,
+        referral,
+        leg_min_outs,
     )
 }
 
+pub fn execute_batch_redeem(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    redemptions: Vec<Addr>,
+    recipient: Option<String>,
+    receive_choice: ReceiveChoice,
+    min_out: AssetList,
+) -> Result<Response, ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidBatch {
+        reason: reason.to_string(),
+    };
+    if redemptions.is_empty() {
+        return Err(invalid("no redemptions"));
+    }
+    // Exactly one vault token must be sent per vault
+    if info.funds.len() != redemptions.len() {
+        return Err(ContractError::InvalidVaultToken {});
+    }
+
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
+
+    let touched_assets = withdraw_touched_assets(deps.as_ref(), &redemptions)?;
+    let return_leftovers_msg = reserve_balances(
+        deps.branch(),
+        &env,
+        touched_assets,
+        &AssetList::new(),
+        &info.sender,
+    )?;
+
+    // Redeem from each vault without enforcing a minimum amount received, which
+    // is instead enforced once on the combined assets at the end
+    let mut responses = vec![];
+    for (i, vault_address) in redemptions.iter().enumerate() {
+        if redemptions[..i].contains(vault_address) {
+            return Err(invalid("duplicate vault"));
+        }
+        let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, vault_address)?;
+        let coin = info
+            .funds
+            .iter()
+            .find(|coin| coin.denom == vault.vault_token)
+            .ok_or(ContractError::InvalidVaultToken {})?;
+
+        responses.push(redeem(
+            deps.branch(),
+            env.clone(),
+            info.sender.clone(),
+            vault_address.clone(),
+            Asset::native(&coin.denom, coin.amount),
+            Some(recipient.to_string()),
+            receive_choice.clone(),
+            None,
+            None,
+            LegMinOuts::default(),
+        )?);
+    }
+
+    let enforce_min_out_msg = combined_min_out_msg(deps.as_ref(), &env, &recipient, &min_out)?;
+
+    let event = Event::new("apollo/vault-zapper/execute_batch_redeem")
+        .add_attribute("redemptions", to_json_binary(&redemptions)?.to_string())
+        .add_attribute("recipient", &recipient)
+        .add_attribute(
+            "receive_choice",
+            to_json_binary(&receive_choice)?.to_string(),
+        )
+        .add_attribute("min_out", to_json_binary(&min_out)?.to_string());
+
+    Ok(merge_responses(responses)
+        .add_message(enforce_min_out_msg)
+        .add_message(return_leftovers_msg)
+        .add_event(event))
+}
+
+/// Returns a message that enforces that `recipient` receives at least
+/// `min_out` in total from the withdrawals of a batch, which are made without a
+/// minimum amount of their own.
+fn combined_min_out_msg(
+    deps: Deps,
+    env: &Env,
+    recipient: &Addr,
+    min_out: &AssetList,
+) -> Result<CosmosMsg, ContractError> {
+    let assets: Vec<AssetInfo> = min_out.iter().map(|a| a.info.clone()).collect();
+    let balances_before =
+        AssetList::query_asset_info_balances(assets.clone(), &deps.querier, recipient)?;
+
+    Ok(CallbackMsg::EnforceMinOut {
+        assets,
+        recipient: recipient.clone(),
+        balances_before,
+        min_out: min_out.clone(),
+        offer_assets: AssetList::new(),
+    }
+    .into_cosmos_msg(env)?)
+}
+
 pub fn execute_withdraw_unlocked(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     vault_address: Addr,
@@ -50,74 +248,271 @@ pub fn execute_withdraw_unlocked(
     recipient: Option<String>,
     receive_choice: ReceiveChoice,
     min_out: AssetList,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
 ) -> Result<Response, ContractError> {
-    let key = LOCKUP_IDS.key((info.sender.clone(), vault_address.clone(), lockup_id));
+    let withdraw_unlocked_msg =
+        take_unlocking_position(deps.branch(), &info.sender, &vault_address, lockup_id)?;
+    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
 
-    // Check if lockup ID is valid.
-    if !key.has(deps.storage) {
-        return Err(ContractError::Unauthorized {});
-    }
+    let touched_assets = withdraw_touched_assets(deps.as_ref(), &[vault_address])?;
+    let return_leftovers_msg = reserve_balances(
+        deps.branch(),
+        &env,
+        touched_assets,
+        &AssetList::new(),
+        &info.sender,
+    )?;
 
-    // Remove lockup ID from users lockup IDs.
-    key.remove(deps.storage);
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(info.sender), |x| deps.api.addr_validate(&x))?;
 
     // Proceed with normal withdraw
-    withdraw(
+    let res = withdraw(
         deps,
         env,
-        info,
-        vault_address,
+        vault,
+        withdraw_unlocked_msg,
         recipient,
         receive_choice,
-        min_out,
+        Some(min_out),
         RedeemType::Lockup(lockup_id),
-    )
+        referral,
+        leg_min_outs,
+    )?;
+
+    Ok(res.add_message(return_leftovers_msg))
 }
 
-// Called by execute_withdraw and execute_withdraw_unlocked to withdraw assets
-// from the vault.
-pub fn withdraw(
-    deps: DepsMut,
+pub fn execute_withdraw_unlocked_many(
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    vault_address: Addr,
+    positions: Vec<(Addr, u64)>,
     recipient: Option<String>,
     receive_choice: ReceiveChoice,
     min_out: AssetList,
-    withdraw_type: RedeemType,
 ) -> Result<Response, ContractError> {
+    if positions.is_empty() {
+        return Err(ContractError::InvalidBatch {
+            reason: "no positions".to_string(),
+        });
+    }
+
     // Unwrap recipient or use sender
-    let recipient = recipient.map_or(Ok(info.sender), |x| deps.api.addr_validate(&x))?;
+    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
 
-    // Query the vault info
-    let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, &vault_address)?;
-    let vault_token_denom = &vault.vault_token;
-    let vault_base_token = match deps.api.addr_validate(&vault.base_token) {
-        Ok(addr) => AssetInfo::cw20(addr),
-        Err(_) => AssetInfo::native(&vault.base_token),
-    };
+    let res = withdraw_unlocked_many(
+        deps.branch(),
+        &env,
+        &info.sender,
+        &positions,
+        &recipient,
+        &receive_choice,
+    )?;
+    let enforce_min_out_msg = combined_min_out_msg(deps.as_ref(), &env, &recipient, &min_out)?;
+    let return_leftovers_msg =
+        reserve_withdraw_unlocked_balances(deps, &env, &positions, &info.sender)?;
 
-    // Get withdraw msg
-    let withdraw_msg = match withdraw_type {
-        RedeemType::Normal => {
-            // Make sure vault token was sent
-            if info.funds.len() != 1 || &info.funds[0].denom != vault_token_denom {
-                return Err(ContractError::InvalidVaultToken {});
-            }
-            let vault_token = info.funds[0].clone();
+    let event = Event::new("apollo/vault-zapper/execute_withdraw_unlocked_many")
+        .add_attribute("positions", to_json_binary(&positions)?.to_string())
+        .add_attribute("recipient", &recipient)
+        .add_attribute(
+            "receive_choice",
+            to_json_binary(&receive_choice)?.to_string(),
+        )
+        .add_attribute("min_out", to_json_binary(&min_out)?.to_string());
 
-            vault.redeem(vault_token.amount, None)?
+    Ok(res
+        .add_message(enforce_min_out_msg)
+        .add_message(return_leftovers_msg)
+        .add_event(event))
+}
+
+pub fn execute_withdraw_all_matured(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vault_address: Option<Addr>,
+    recipient: Option<String>,
+    receive_choice: ReceiveChoice,
+    min_out: AssetList,
+    start_after_vault_addr: Option<String>,
+    start_after_id: Option<u64>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    // Find the sender's unlocking positions, optionally only of one vault, that
+    // have finished unlocking among the next `limit` positions
+    let lockup_ids: Vec<(Addr, u64)> = match &vault_address {
+        Some(vault_address) => LOCKUP_IDS
+            .prefix((info.sender.clone(), vault_address.clone()))
+            .keys(
+                deps.storage,
+                start_after_id.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit.unwrap_or(DEFAULT_LIMIT) as usize)
+            .map(|id| Ok((vault_address.clone(), id?)))
+            .collect::<StdResult<_>>()?,
+        None => paginate_all_user_unlocking_positions(
+            deps.as_ref(),
+            info.sender.clone(),
+            start_after_vault_addr,
+            start_after_id,
+            limit,
+        )?
+        .map(|item| Ok(item?.0))
+        .collect::<StdResult<_>>()?,
+    };
+    let mut positions = vec![];
+    for (vault_address, lockup_id) in lockup_ids {
+        let unlocking_position =
+            query_unlocking_position(deps.as_ref(), &vault_address, lockup_id)?;
+        if unlocking_position.release_at.is_expired(&env.block) {
+            positions.push((vault_address, lockup_id));
         }
-        RedeemType::Lockup(lockup_id) => CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: vault_address.to_string(),
-            funds: vec![],
-            msg: to_json_binary(&VaultExecuteMsg::<ExtensionExecuteMsg>::VaultExtension(
-                ExtensionExecuteMsg::Lockup(LockupExecuteMsg::WithdrawUnlocked {
-                    recipient: None,
-                    lockup_id,
-                }),
-            ))?,
-        }),
+    }
+    if positions.is_empty() {
+        return Err(ContractError::Generic(
+            "No matured unlocking positions".to_string(),
+        ));
+    }
+
+    // Unwrap recipient or use sender
+    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
+
+    let res = withdraw_unlocked_many(
+        deps.branch(),
+        &env,
+        &info.sender,
+        &positions,
+        &recipient,
+        &receive_choice,
+    )?;
+    let enforce_min_out_msg = combined_min_out_msg(deps.as_ref(), &env, &recipient, &min_out)?;
+    let return_leftovers_msg =
+        reserve_withdraw_unlocked_balances(deps, &env, &positions, &info.sender)?;
+
+    let event = Event::new("apollo/vault-zapper/execute_withdraw_all_matured")
+        .add_attribute("positions", to_json_binary(&positions)?.to_string())
+        .add_attribute("recipient", &recipient)
+        .add_attribute(
+            "receive_choice",
+            to_json_binary(&receive_choice)?.to_string(),
+        )
+        .add_attribute("min_out", to_json_binary(&min_out)?.to_string());
+
+    Ok(res
+        .add_message(enforce_min_out_msg)
+        .add_message(return_leftovers_msg)
+        .add_event(event))
+}
+
+/// Withdraws each of the `owner`'s unlocking `positions`, given as (vault
+/// address, lockup id), and handles the withdrawn base tokens according to
+/// `receive_choice`. No minimum amount is enforced on the individual
+/// withdrawals.
+fn withdraw_unlocked_many(
+    mut deps: DepsMut,
+    env: &Env,
+    owner: &Addr,
+    positions: &[(Addr, u64)],
+    recipient: &Addr,
+    receive_choice: &ReceiveChoice,
+) -> Result<Response, ContractError> {
+    let mut responses = vec![];
+    for (vault_address, lockup_id) in positions {
+        let withdraw_unlocked_msg =
+            take_unlocking_position(deps.branch(), owner, vault_address, *lockup_id)?;
+        let vault: VaultContract<Empty, Empty> = VaultContract::new(&deps.querier, vault_address)?;
+
+        responses.push(withdraw(
+            deps.branch(),
+            env.clone(),
+            vault,
+            withdraw_unlocked_msg,
+            recipient.clone(),
+            receive_choice.clone(),
+            None,
+            RedeemType::Lockup(*lockup_id),
+            None,
+            LegMinOuts::default(),
+        )?);
+    }
+
+    Ok(merge_responses(responses))
+}
+
+/// Reserves the contract's balances of the assets touched by withdrawing the
+/// unlocking `positions` and returns the `ReturnLeftovers` message that ends
+/// the withdrawals.
+fn reserve_withdraw_unlocked_balances(
+    deps: DepsMut,
+    env: &Env,
+    positions: &[(Addr, u64)],
+    sender: &Addr,
+) -> Result<CosmosMsg, ContractError> {
+    let vault_addresses: Vec<Addr> = positions.iter().map(|(addr, _)| addr.clone()).collect();
+    let touched_assets = withdraw_touched_assets(deps.as_ref(), &vault_addresses)?;
+
+    reserve_balances(deps, env, touched_assets, &AssetList::new(), sender)
+}
+
+/// Removes the unlocking position `lockup_id` in the vault from the positions
+/// of `owner` and returns a message that withdraws it to the contract. Errors
+/// if `owner` does not own the position.
+pub fn take_unlocking_position(
+    deps: DepsMut,
+    owner: &Addr,
+    vault_address: &Addr,
+    lockup_id: u64,
+) -> Result<CosmosMsg, ContractError> {
+    let key = LOCKUP_IDS.key((owner.clone(), vault_address.clone(), lockup_id));
+
+    // Check if lockup ID is valid.
+    if !key.has(deps.storage) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Remove lockup ID from users lockup IDs.
+    key.remove(deps.storage);
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: vault_address.to_string(),
+        funds: vec![],
+        msg: to_json_binary(&VaultExecuteMsg::<ExtensionExecuteMsg>::VaultExtension(
+            ExtensionExecuteMsg::Lockup(LockupExecuteMsg::WithdrawUnlocked {
+                recipient: None,
+                lockup_id,
+            }),
+        ))?,
+    }))
+}
+
+// Called by redeem and execute_withdraw_unlocked to withdraw assets from the
+// vault with `withdraw_msg` and handle the withdrawn base tokens. `min_out` is
+// `None` for the withdrawals of a batch.
+pub fn withdraw(
+    deps: DepsMut,
+    env: Env,
+    vault: VaultContract<Empty, Empty>,
+    withdraw_msg: CosmosMsg,
+    recipient: Addr,
+    receive_choice: ReceiveChoice,
+    min_out: Option<AssetList>,
+    withdraw_type: RedeemType,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    let vault_address = vault.addr.clone();
+    receive_choice.check()?;
+
+    let fees = Fees::resolve(deps.as_ref(), Some(&vault_address), referral.as_ref())?;
+
+    let vault_base_token = match deps.api.addr_validate(&vault.base_token) {
+        Ok(addr) => AssetInfo::cw20(addr),
+        Err(_) => AssetInfo::native(&vault.base_token),
     };
 
     let event = Event::new("apollo/vault-zapper/withdraw")
@@ -128,7 +523,8 @@ pub fn withdraw(
             to_json_binary(&receive_choice)?.to_string(),
         )
         .add_attribute("withdraw_type", to_json_binary(&withdraw_type)?.to_string())
-        .add_attribute("min_out", to_json_binary(&min_out)?.to_string());
+        .add_attribute("min_out", to_json_binary(&min_out)?.to_string())
+        .add_attribute("leg_min_outs", to_json_binary(&leg_min_outs)?.to_string());
 
     Ok(Response::new()
         .add_message(withdraw_msg)
@@ -138,6 +534,8 @@ pub fn withdraw(
                 vault_base_token,
                 recipient,
                 min_out,
+                fees,
+                leg_min_outs,
             }
             .into_cosmos_msg(&env)?,
         )
@@ -145,18 +543,66 @@ pub fn withdraw(
 }
 
 pub fn execute_zap_base_tokens(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     base_token: Asset,
     recipient: Option<String>,
     receive_choice: ReceiveChoice,
     min_out: AssetList,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
 ) -> Result<Response, ContractError> {
+    let receive_assets_res = receive_assets(&info, &env, &vec![base_token.clone()].into())?;
+
+    // Reserve the contract's balances of the base token and the assets it is
+    // made of, excluding the native tokens sent with this message
+    let touched_assets = base_token_assets(deps.as_ref(), &base_token.info)?;
+    let return_leftovers_msg = reserve_balances(
+        deps.branch(),
+        &env,
+        touched_assets,
+        &info.funds.clone().into(),
+        &info.sender,
+    )?;
+
+    let zap_res = zap_base_tokens(
+        deps,
+        env,
+        info.sender,
+        base_token,
+        recipient,
+        receive_choice,
+        min_out,
+        referral,
+        leg_min_outs,
+    )?;
+
+    Ok(merge_responses(vec![receive_assets_res, zap_res]).add_message(return_leftovers_msg))
+}
+
+/// Zaps `base_token`, which must already be held by the contract, according to
+/// `receive_choice`. Called by `execute_zap_base_tokens` and by the CW20
+/// receive hook.
+pub fn zap_base_tokens(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    base_token: Asset,
+    recipient: Option<String>,
+    receive_choice: ReceiveChoice,
+    min_out: AssetList,
+    referral: Option<Referral>,
+    leg_min_outs: LegMinOuts,
+) -> Result<Response, ContractError> {
+    receive_choice.check()?;
+
     // Unwrap recipient or use sender
-    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
+    let recipient = recipient.map_or(Ok(sender), |x| deps.api.addr_validate(&x))?;
 
-    let receive_assets_res = receive_assets(&info, &env, &vec![base_token.clone()].into())?;
+    // There is no vault to look up a fee override for, so the default protocol
+    // fee applies
+    let fees = Fees::resolve(deps.as_ref(), None, referral.as_ref())?;
 
     let event = Event::new("apollo/vault-zapper/execute_zap_base_tokens")
         .add_attribute("base_token", to_json_binary(&base_token.info)?.to_string())
@@ -165,15 +611,18 @@ pub fn execute_zap_base_tokens(
             "receive_choice",
             to_json_binary(&receive_choice)?.to_string(),
         )
-        .add_attribute("min_out", to_json_binary(&min_out)?.to_string());
+        .add_attribute("min_out", to_json_binary(&min_out)?.to_string())
+        .add_attribute("leg_min_outs", to_json_binary(&leg_min_outs)?.to_string());
 
-    Ok(receive_assets_res
+    Ok(Response::new()
         .add_message(
             CallbackMsg::AfterRedeem {
                 receive_choice,
                 vault_base_token: base_token.info,
                 recipient,
-                min_out,
+                min_out: Some(min_out),
+                fees,
+                leg_min_outs,
             }
             .into_cosmos_msg(&env)?,
         )
@@ -186,20 +635,94 @@ pub fn callback_after_redeem(
     receive_choice: ReceiveChoice,
     vault_base_token: AssetInfo,
     recipient: Addr,
-    min_out: AssetList,
+    min_out: Option<AssetList>,
+    fees: Fees,
+    leg_min_outs: LegMinOuts,
 ) -> Result<Response, ContractError> {
-    // Check contract's balance of vault's base token
-    let base_token_balance =
-        vault_base_token.query_balance(&deps.querier, &env.contract.address)?;
-    let base_token = Asset::new(vault_base_token.clone(), base_token_balance);
+    // Check contract's unreserved balance of vault's base token
+    let base_token =
+        query_unreserved_balances(deps.as_ref(), &env, vec![vault_base_token.clone()])?
+            .find(&vault_base_token)
+            .cloned()
+            .unwrap_or_else(|| Asset::new(vault_base_token.clone(), Uint128::zero()));
 
+    // If the base token is an LP token, liquidity is withdrawn from its pool
+    // and then from the pools of any LP tokens among the withdrawn assets
     let astroport_liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.may_load(deps.storage)?;
-    let pool = Pool::get_pool_for_lp_token(
+    let mut pools = get_nested_pools(
         deps.as_ref(),
         &vault_base_token,
-        astroport_liquidity_manager,
-    )
-    .ok();
+        astroport_liquidity_manager.clone(),
+    )?
+    .into_iter();
+    let pool = pools.next();
+    let inner_pools: Vec<Pool> = pools.collect();
+
+    // If the base token is the vault token of another vault, redeem it from that
+    // vault and handle the redeemed base tokens in another AfterRedeem callback,
+    // which also takes the fees and enforces the minimum amount received
+    if pool.is_none() {
+        if let Some((inner_vault, inner_base_token, inner_receive_choice)) =
+            get_inner_vault_redemption(
+                deps.as_ref(),
+                &vault_base_token,
+                &receive_choice,
+                astroport_liquidity_manager,
+            )?
+        {
+            let event = Event::new("apollo/vault-zapper/callback_after_redeem")
+                .add_attribute("base_token", to_json_binary(&base_token)?.to_string())
+                .add_attribute("inner_vault", &inner_vault.addr);
+            return Ok(Response::new()
+                .add_message(inner_vault.redeem_vault_tokens(&base_token)?)
+                .add_message(
+                    CallbackMsg::AfterRedeem {
+                        receive_choice: inner_receive_choice,
+                        vault_base_token: inner_base_token,
+                        recipient,
+                        min_out,
+                        fees,
+                        leg_min_outs,
+                    }
+                    .into_cosmos_msg(&env)?,
+                )
+                .add_event(event));
+        }
+    }
+
+    // If liquidity is withdrawn from the base token, the fees are taken from the
+    // withdrawn assets in callback_after_withdraw_liq. Otherwise we take them
+    // from the base token here.
+    let withdraws_liquidity = pool.is_some()
+        && match &receive_choice {
+            ReceiveChoice::SwapTo(requested_asset) => requested_asset != &vault_base_token,
+            ReceiveChoice::Underlying | ReceiveChoice::Basket(_) => true,
+            ReceiveChoice::BaseToken => false,
+        };
+    let (fee_msgs, base_token, fee_attrs) = if withdraws_liquidity {
+        (vec![], base_token, vec![])
+    } else {
+        let (fee_msgs, base_tokens, fee_attrs) = fees.take_from_assets(&vec![base_token].into())?;
+        let amount = base_tokens
+            .find(&vault_base_token)
+            .map(|x| x.amount)
+            .unwrap_or_default();
+        (
+            fee_msgs,
+            Asset::new(vault_base_token.clone(), amount),
+            fee_attrs,
+        )
+    };
+    // The base tokens after fees are given in exchange for the withdrawal
+    // assets, which is checked against oracle prices in EnforceMinOut
+    let offer_assets: AssetList = if withdraws_liquidity {
+        fees.take_from_assets(&vec![base_token.clone()].into())?.1
+    } else {
+        vec![base_token.clone()].into()
+    };
+    let event = Event::new("apollo/vault-zapper/callback_after_redeem")
+        .add_attribute("base_token", to_json_binary(&base_token)?.to_string())
+        .add_attributes(fee_attrs);
 
     // Check requested withdrawal assets
     let (res, withdrawal_assets) = match &receive_choice {
@@ -217,14 +740,21 @@ pub fn callback_after_redeem(
 
                 if let Some(pool) = pool {
                     // Add messages to withdraw liquidity
-                    let withdraw_liq_res =
-                        pool.withdraw_liquidity(deps.as_ref(), &env, base_token, AssetList::new())?;
+                    let withdraw_liq_res = pool.withdraw_liquidity(
+                        deps.as_ref(),
+                        &env,
+                        base_token,
+                        leg_min_outs.withdraw_liquidity,
+                    )?;
                     Ok((
                         withdraw_liq_res.add_message(
                             CallbackMsg::AfterWithdrawLiq {
                                 assets: pool.pool_assets(deps.as_ref())?,
                                 receive_choice: receive_choice.clone(),
                                 recipient: recipient.clone(),
+                                fees,
+                                inner_pools,
+                                swap_min_outs: leg_min_outs.swap,
                             }
                             .into_cosmos_msg(&env)?,
                         ),
@@ -232,10 +762,17 @@ pub fn callback_after_redeem(
                     ))
                 } else {
                     // Basket liquidate the asset withdrawn from the vault
+                    let offer_assets: AssetList = vec![base_token].into();
+                    let min_receive = swap_min_receive(
+                        deps.as_ref(),
+                        &offer_assets,
+                        requested_asset,
+                        leg_min_outs.swap_min_out(requested_asset),
+                    )?;
                     let msgs = router.basket_liquidate_msgs(
-                        vec![base_token].into(),
+                        offer_assets,
                         requested_asset,
-                        None, // Not needed as we have our own min_out enforcement
+                        min_receive,
                         Some(recipient.to_string()),
                     )?;
                     Ok((
@@ -253,37 +790,120 @@ pub fn callback_after_redeem(
             if let Some(pool) = pool {
                 let pool_assets = pool.pool_assets(deps.as_ref())?;
 
-                let res =
-                    pool.withdraw_liquidity(deps.as_ref(), &env, base_token, AssetList::new())?;
+                // The recipient receives the leaf assets of the pools, since
+                // liquidity is withdrawn from the LP tokens of inner pools too
+                let mut leaf_assets = pool_assets.clone();
+                for inner_pool in &inner_pools {
+                    let inner_lp_token = inner_pool.lp_token();
+                    leaf_assets.retain(|info| info != &inner_lp_token);
+                    for info in inner_pool.pool_assets(deps.as_ref())? {
+                        if !leaf_assets.contains(&info) {
+                            leaf_assets.push(info);
+                        }
+                    }
+                }
+
+                let res = pool.withdraw_liquidity(
+                    deps.as_ref(),
+                    &env,
+                    base_token,
+                    leg_min_outs.withdraw_liquidity,
+                )?;
                 Ok((
                     res.add_message(
                         CallbackMsg::AfterWithdrawLiq {
-                            assets: pool_assets.clone(),
+                            assets: pool_assets,
                             receive_choice,
                             recipient: recipient.clone(),
+                            fees,
+                            inner_pools,
+                            swap_min_outs: leg_min_outs.swap,
                         }
                         .into_cosmos_msg(&env)?,
                     ),
-                    pool_assets,
+                    leaf_assets,
                 ))
             } else {
                 Err(ContractError::UnsupportedWithdrawal {})
             }
         }
+        ReceiveChoice::Basket(basket) => {
+            let basket_assets: Vec<AssetInfo> =
+                basket.iter().map(|(info, _)| info.clone()).collect();
+            if let Some(pool) = pool {
+                // The base token can not be received after withdrawing liquidity
+                if basket_assets.contains(&vault_base_token) {
+                    return Err(ContractError::InvalidBasket {
+                        reason: "can not contain the base token when it is an LP token".to_string(),
+                    });
+                }
+
+                // Withdraw liquidity and split the withdrawn assets in
+                // callback_after_withdraw_liq
+                let res = pool.withdraw_liquidity(
+                    deps.as_ref(),
+                    &env,
+                    base_token,
+                    leg_min_outs.withdraw_liquidity,
+                )?;
+                Ok((
+                    res.add_message(
+                        CallbackMsg::AfterWithdrawLiq {
+                            assets: pool.pool_assets(deps.as_ref())?,
+                            receive_choice: receive_choice.clone(),
+                            recipient: recipient.clone(),
+                            fees,
+                            inner_pools,
+                            swap_min_outs: leg_min_outs.swap,
+                        }
+                        .into_cosmos_msg(&env)?,
+                    ),
+                    basket_assets,
+                ))
+            } else {
+                let router = ROUTER.load(deps.storage)?;
+                let msgs = basket_msgs(
+                    deps.as_ref(),
+                    &router,
+                    &vec![base_token].into(),
+                    basket,
+                    &recipient,
+                    &leg_min_outs.swap,
+                )?;
+                Ok((Response::new().add_messages(msgs), basket_assets))
+            }
+        }
     }?;
 
-    // Add a message to enforce the minimum amount of assets received
-    let balances_before =
-        AssetList::query_asset_info_balances(withdrawal_assets.clone(), &deps.querier, &recipient)?;
-    let enforce_min_out_msg = CallbackMsg::EnforceMinOut {
-        assets: withdrawal_assets,
-        recipient: recipient.clone(),
-        balances_before,
-        min_out: min_out.clone(),
-    }
-    .into_cosmos_msg(&env)?;
+    let res = Response::new()
+        .add_messages(fee_msgs)
+        .add_submessages(res.messages)
+        .add_events(res.events);
+
+    // Add a message to enforce the minimum amount of assets received. The
+    // redemptions of a batch, whose minimum amount is enforced on the combined
+    // assets, only need it to check the oracle rate.
+    let res = if min_out.is_some() || ORACLE_CONFIG.may_load(deps.storage)?.is_some() {
+        let balances_before = AssetList::query_asset_info_balances(
+            withdrawal_assets.clone(),
+            &deps.querier,
+            &recipient,
+        )?;
+        res.add_message(
+            CallbackMsg::EnforceMinOut {
+                assets: withdrawal_assets,
+                recipient: recipient.clone(),
+                balances_before,
+                min_out: min_out.unwrap_or_default(),
+                offer_assets,
+            }
+            .into_cosmos_msg(&env)?,
+        )
+    } else {
+        res
+    };
 
-    Ok(res.add_message(enforce_min_out_msg))
+    Ok(res.add_event(event))
 }
 
 pub fn callback_after_withdraw_liq(
@@ -292,11 +912,60 @@ pub fn callback_after_withdraw_liq(
     assets: Vec<AssetInfo>,
     receive_choice: ReceiveChoice,
     recipient: Addr,
+    fees: Fees,
+    inner_pools: Vec<Pool>,
+    swap_min_outs: AssetList,
 ) -> Result<Response, ContractError> {
     let router = ROUTER.load(deps.storage)?;
 
-    let asset_balances =
-        AssetList::query_asset_info_balances(assets, &deps.querier, &env.contract.address)?;
+    let asset_balances = query_unreserved_balances(deps.as_ref(), &env, assets.clone())?;
+
+    // If one of the withdrawn assets is the LP token of an inner pool, withdraw
+    // liquidity from that pool as well, unless the LP token was requested
+    if let Some((pool, inner_pools)) = inner_pools.split_first() {
+        let requested_assets = match &receive_choice {
+            ReceiveChoice::SwapTo(requested_asset) => vec![requested_asset.clone()],
+            ReceiveChoice::Basket(basket) => basket.iter().map(|(info, _)| info.clone()).collect(),
+            ReceiveChoice::Underlying | ReceiveChoice::BaseToken => vec![],
+        };
+        let lp_token = pool.lp_token();
+        if let Some(lp_token) = asset_balances.find(&lp_token) {
+            if !lp_token.amount.is_zero() && !requested_assets.contains(&lp_token.info) {
+                let mut assets: Vec<AssetInfo> =
+                    assets.into_iter().filter(|a| a != &lp_token.info).collect();
+                for info in pool.pool_assets(deps.as_ref())? {
+                    if !assets.contains(&info) {
+                        assets.push(info);
+                    }
+                }
+
+                let res = pool.withdraw_liquidity(
+                    deps.as_ref(),
+                    &env,
+                    lp_token.clone(),
+                    AssetList::new(),
+                )?;
+                return Ok(res.add_message(
+                    CallbackMsg::AfterWithdrawLiq {
+                        assets,
+                        receive_choice,
+                        recipient,
+                        fees,
+                        inner_pools: inner_pools.to_vec(),
+                        swap_min_outs,
+                    }
+                    .into_cosmos_msg(&env)?,
+                ));
+            }
+        }
+    }
+
+    // Take the fees from the withdrawn assets
+    let (fee_msgs, asset_balances, fee_attrs) = fees.take_from_assets(&asset_balances)?;
+    let event = Event::new("apollo/vault-zapper/callback_after_withdraw_liq")
+        .add_attribute("assets", to_json_binary(&asset_balances)?.to_string())
+        .add_attributes(fee_attrs);
+    let res = Response::new().add_messages(fee_msgs).add_event(event);
 
     match receive_choice {
         ReceiveChoice::SwapTo(requested_asset) => {
@@ -306,15 +975,22 @@ pub fn callback_after_withdraw_liq(
 
             // Add messages to basket liquidate the assets withdrawn from the LP, but filter
             // out the requested asset as we can't swap an asset to itself.
+            let offer_assets: AssetList = asset_balances
+                .to_vec()
+                .into_iter()
+                .filter(|x| x.info != requested_asset)
+                .collect::<Vec<_>>()
+                .into();
+            let min_receive = swap_min_receive(
+                deps.as_ref(),
+                &offer_assets,
+                &requested_asset,
+                swap_min_outs.find(&requested_asset).map(|x| x.amount),
+            )?;
             let mut msgs = router.basket_liquidate_msgs(
-                asset_balances
-                    .to_vec()
-                    .into_iter()
-                    .filter(|x| x.info != requested_asset)
-                    .collect::<Vec<_>>()
-                    .into(),
+                offer_assets,
                 &requested_asset,
-                None, // Not needed as we have our own min_out enforcement
+                min_receive,
                 Some(recipient.to_string()),
             )?;
 
@@ -326,14 +1002,71 @@ pub fn callback_after_withdraw_liq(
                 );
             }
 
-            Ok(Response::new().add_messages(msgs))
+            Ok(res.add_messages(msgs))
         }
         ReceiveChoice::Underlying => {
             let msgs = asset_balances.transfer_msgs(recipient)?;
-            Ok(Response::new().add_messages(msgs))
+            Ok(res.add_messages(msgs))
+        }
+        ReceiveChoice::Basket(basket) => {
+            let msgs = basket_msgs(
+                deps.as_ref(),
+                &router,
+                &asset_balances,
+                &basket,
+                &recipient,
+                &swap_min_outs,
+            )?;
+            Ok(res.add_messages(msgs))
         }
         ReceiveChoice::BaseToken => {
             panic!("Should not be possible to receive base token from callback_after_withdraw_liq")
         }
     }
 }
+
+/// Returns messages that split `assets` according to the weights of `basket`
+/// and send each part to `recipient`. Parts that are not already the asset of
+/// their basket entry are basket liquidated into it, receiving at least the
+/// amount of the asset in `swap_min_outs`, if any, and the amount implied by
+/// oracle prices.
+fn basket_msgs(
+    deps: Deps,
+    router: &CwDexRouter,
+    assets: &AssetList,
+    basket: &[(AssetInfo, Decimal)],
+    recipient: &Addr,
+    swap_min_outs: &AssetList,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut msgs = vec![];
+
+    for (target, part) in split_by_weights(assets, basket)? {
+        let offer_assets: AssetList = part
+            .iter()
+            .filter(|a| a.info != target)
+            .cloned()
+            .collect::<Vec<_>>()
+            .into();
+        if offer_assets.len() > 0 {
+            let min_receive = swap_min_receive(
+                deps,
+                &offer_assets,
+                &target,
+                swap_min_outs.find(&target).map(|x| x.amount),
+            )?;
+            msgs.extend(router.basket_liquidate_msgs(
+                offer_assets,
+                &target,
+                min_receive,
+                Some(recipient.to_string()),
+            )?);
+        }
+        if let Some(direct) = part.find(&target) {
+            if !direct.amount.is_zero() {
+                msgs.push(direct.transfer_msg(recipient)?);
+            }
+        }
+    }
+
+    Ok(msgs)
+}
@@ -0,0 +1,195 @@
+//! A minimal cw-vault-standard vault with the lockup extension that wraps a
+//! native token 1:1 into a CW20 vault token issued by the vault contract
+//! itself. Used to test vaults with a CW20 vault token.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    coin, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Env, Event, MessageInfo,
+    Response, StdError, StdResult, Uint128,
+};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
+use cw_it::cw_multi_test::ContractWrapper;
+use cw_it::test_tube::{Module, SigningAccount, Wasm};
+use cw_it::traits::CwItRunner;
+use cw_it::{ContractType, TestRunner};
+use cw_storage_plus::{Item, Map};
+use cw_utils::{must_pay, Expiration};
+use cw_vault_standard::extensions::lockup::{
+    LockupExecuteMsg, LockupQueryMsg, UnlockingPosition, UNLOCKING_POSITION_ATTR_KEY,
+    UNLOCKING_POSITION_CREATED_EVENT_TYPE,
+};
+use cw_vault_standard::{
+    ExtensionExecuteMsg, ExtensionQueryMsg, VaultInfoResponse, VaultStandardExecuteMsg,
+    VaultStandardQueryMsg,
+};
+
+const BASE_TOKEN: Item<String> = Item::new("base_token");
+const BALANCES: Map<&Addr, Uint128> = Map::new("balances");
+const POSITIONS: Map<u64, UnlockingPosition> = Map::new("positions");
+const NEXT_LOCKUP_ID: Item<u64> = Item::new("next_lockup_id");
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    /// The native denom to wrap
+    pub base_token: String,
+}
+
+#[cw_serde]
+#[serde(untagged)]
+enum ExecuteMsg {
+    Vault(VaultStandardExecuteMsg<ExtensionExecuteMsg>),
+    Cw20(Cw20ExecuteMsg),
+}
+
+#[cw_serde]
+#[serde(untagged)]
+enum QueryMsg {
+    Vault(VaultStandardQueryMsg<ExtensionQueryMsg>),
+    Cw20(Cw20QueryMsg),
+}
+
+fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> StdResult<Response> {
+    BASE_TOKEN.save(deps.storage, &msg.base_token)?;
+    NEXT_LOCKUP_ID.save(deps.storage, &0)?;
+    Ok(Response::new())
+}
+
+fn update_balance(
+    deps: DepsMut,
+    address: &Addr,
+    f: impl FnOnce(Uint128) -> StdResult<Uint128>,
+) -> StdResult<()> {
+    let balance = BALANCES
+        .may_load(deps.storage, address)?
+        .unwrap_or_default();
+    BALANCES.save(deps.storage, address, &f(balance)?)
+}
+
+fn execute(mut deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+    let base_token = BASE_TOKEN.load(deps.storage)?;
+    let burn = |amount: Uint128| move |b: Uint128| Ok(b.checked_sub(amount)?);
+    let mint = |amount: Uint128| move |b: Uint128| Ok(b + amount);
+
+    // Vault tokens are minted and burned 1:1 with the base token
+    match msg {
+        ExecuteMsg::Vault(VaultStandardExecuteMsg::Deposit { amount, recipient }) => {
+            if must_pay(&info, &base_token).map_err(|e| StdError::generic_err(e.to_string()))?
+                != amount
+            {
+                return Err(StdError::generic_err("Invalid deposit amount"));
+            }
+            let recipient = deps
+                .api
+                .addr_validate(&recipient.unwrap_or(info.sender.to_string()))?;
+            update_balance(deps, &recipient, mint(amount))?;
+            Ok(Response::new())
+        }
+        ExecuteMsg::Vault(VaultStandardExecuteMsg::Redeem { recipient, amount }) => {
+            update_balance(deps, &info.sender, burn(amount))?;
+            Ok(Response::new().add_message(BankMsg::Send {
+                to_address: recipient.unwrap_or(info.sender.to_string()),
+                amount: vec![coin(amount.u128(), base_token)],
+            }))
+        }
+        ExecuteMsg::Vault(VaultStandardExecuteMsg::VaultExtension(
+            ExtensionExecuteMsg::Lockup(LockupExecuteMsg::Unlock { amount }),
+        )) => {
+            update_balance(deps.branch(), &info.sender, burn(amount))?;
+            let id = NEXT_LOCKUP_ID.load(deps.storage)?;
+            NEXT_LOCKUP_ID.save(deps.storage, &(id + 1))?;
+            POSITIONS.save(
+                deps.storage,
+                id,
+                &UnlockingPosition {
+                    id,
+                    owner: info.sender,
+                    release_at: Expiration::AtTime(env.block.time),
+                    base_token_amount: amount,
+                },
+            )?;
+            Ok(Response::new().add_event(
+                Event::new(UNLOCKING_POSITION_CREATED_EVENT_TYPE)
+                    .add_attribute(UNLOCKING_POSITION_ATTR_KEY, id.to_string()),
+            ))
+        }
+        ExecuteMsg::Cw20(Cw20ExecuteMsg::Transfer { recipient, amount }) => {
+            let recipient = deps.api.addr_validate(&recipient)?;
+            update_balance(deps.branch(), &info.sender, burn(amount))?;
+            update_balance(deps, &recipient, mint(amount))?;
+            Ok(Response::new())
+        }
+        ExecuteMsg::Cw20(Cw20ExecuteMsg::Send {
+            contract,
+            amount,
+            msg,
+        }) => {
+            let contract = deps.api.addr_validate(&contract)?;
+            update_balance(deps.branch(), &info.sender, burn(amount))?;
+            update_balance(deps, &contract, mint(amount))?;
+            let receive_msg: CosmosMsg = Cw20ReceiveMsg {
+                sender: info.sender.to_string(),
+                amount,
+                msg,
+            }
+            .into_cosmos_msg(contract)?;
+            Ok(Response::new().add_message(receive_msg))
+        }
+        _ => Err(StdError::generic_err("Unsupported message")),
+    }
+}
+
+fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Vault(VaultStandardQueryMsg::Info {}) => to_json_binary(&VaultInfoResponse {
+            base_token: BASE_TOKEN.load(deps.storage)?,
+            vault_token: env.contract.address.to_string(),
+        }),
+        QueryMsg::Vault(VaultStandardQueryMsg::ConvertToShares { amount })
+        | QueryMsg::Vault(VaultStandardQueryMsg::ConvertToAssets { amount }) => {
+            to_json_binary(&amount)
+        }
+        QueryMsg::Vault(VaultStandardQueryMsg::VaultExtension(ExtensionQueryMsg::Lockup(
+            LockupQueryMsg::UnlockingPosition { lockup_id },
+        ))) => to_json_binary(&POSITIONS.load(deps.storage, lockup_id)?),
+        QueryMsg::Cw20(Cw20QueryMsg::Balance { address }) => to_json_binary(&BalanceResponse {
+            balance: BALANCES
+                .may_load(deps.storage, &deps.api.addr_validate(&address)?)?
+                .unwrap_or_default(),
+        }),
+        _ => Err(StdError::generic_err("Unsupported query")),
+    }
+}
+
+/// Uploads and instantiates a CW20 vault for `base_token` and returns its
+/// address, which is also the address of its CW20 vault token.
+pub fn instantiate_cw20_vault(
+    runner: &TestRunner,
+    base_token: &str,
+    signer: &SigningAccount,
+) -> String {
+    let contract = ContractType::MultiTestContract(Box::new(ContractWrapper::new_with_empty(
+        execute,
+        instantiate,
+        query,
+    )));
+    let code_id = runner.store_code(contract, signer).unwrap();
+    Wasm::new(runner)
+        .instantiate(
+            code_id,
+            &InstantiateMsg {
+                base_token: base_token.to_string(),
+            },
+            None,
+            Some("CW20 Vault"),
+            &[],
+            signer,
+        )
+        .unwrap()
+        .data
+        .address
+}
@@ -0,0 +1,78 @@
+//! A minimal price oracle that returns fixed prices set at instantiation. Used
+//! to test the oracle price checks of the zapper.
+
+use apollo_cw_asset::AssetInfo;
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_json_binary, Binary, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdError,
+    StdResult,
+};
+use cw_it::cw_multi_test::ContractWrapper;
+use cw_it::test_tube::{Module, SigningAccount, Wasm};
+use cw_it::traits::CwItRunner;
+use cw_it::{ContractType, TestRunner};
+use cw_storage_plus::Item;
+use vault_zapper::oracle::{OracleQueryMsg, PriceResponse};
+
+const PRICES: Item<Vec<(AssetInfo, Decimal)>> = Item::new("prices");
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    /// The price of each asset
+    pub prices: Vec<(AssetInfo, Decimal)>,
+}
+
+fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> StdResult<Response> {
+    PRICES.save(deps.storage, &msg.prices)?;
+    Ok(Response::new())
+}
+
+fn execute(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+    Err(StdError::generic_err("Unsupported message"))
+}
+
+fn query(deps: Deps, _env: Env, msg: OracleQueryMsg) -> StdResult<Binary> {
+    match msg {
+        OracleQueryMsg::Price { asset } => {
+            let price = PRICES
+                .load(deps.storage)?
+                .into_iter()
+                .find(|(info, _)| info == &asset)
+                .map(|(_, price)| price)
+                .ok_or_else(|| StdError::generic_err("No price for asset"))?;
+            to_json_binary(&PriceResponse { price })
+        }
+    }
+}
+
+/// Uploads and instantiates a mock oracle with the given prices and returns
+/// its address.
+pub fn instantiate_mock_oracle(
+    runner: &TestRunner,
+    prices: Vec<(AssetInfo, Decimal)>,
+    signer: &SigningAccount,
+) -> String {
+    let contract = ContractType::MultiTestContract(Box::new(ContractWrapper::new_with_empty(
+        execute,
+        instantiate,
+        query,
+    )));
+    let code_id = runner.store_code(contract, signer).unwrap();
+    Wasm::new(runner)
+        .instantiate(
+            code_id,
+            &InstantiateMsg { prices },
+            None,
+            Some("Mock Oracle"),
+            &[],
+            signer,
+        )
+        .unwrap()
+        .data
+        .address
+}
@@ -1,10 +1,23 @@
+pub mod cw20_vault;
+pub mod mock_oracle;
 pub mod robot;
+pub mod wrapper_vault;
+use std::str::FromStr;
+
+use apollo_cw_asset::AssetInfo;
+use cosmwasm_std::Coin;
 use cw_it::cw_multi_test::{StargateKeeper, StargateMessageHandler};
 use cw_it::multi_test::modules::TokenFactory;
 use cw_it::multi_test::MultiTestRunner;
-use cw_it::test_tube::SigningAccount;
+use cw_it::test_tube::{Account, SigningAccount};
 use cw_it::{OwnedTestRunner, TestRunner};
+use liquidity_helper::LiquidityHelperUnchecked;
+use locked_astroport_vault::msg::InstantiateMsg as AstroportVaultInstantiateMsg;
+use locked_astroport_vault_test_helpers::robot::{
+    LockedAstroportVaultRobot, LockedVaultDependencies,
+};
 pub use robot::*;
+use vault_zapper::msg::Pool;
 
 #[cfg(feature = "osmosis-test-tube")]
 use cw_it::osmosis_test_tube::OsmosisTestApp;
@@ -47,3 +60,100 @@ pub fn setup<'a>(
 
     (robot, admin)
 }
+
+/// Returns the instantiate message of a locked Astroport vault of the pool at
+/// `pool_addr`, owned by `owner`, whose rewards are liquidated into
+/// `reward_token`.
+pub fn astroport_vault_instantiate_msg(
+    vault_dependencies: &LockedVaultDependencies,
+    owner: &SigningAccount,
+    pool_addr: String,
+    lock_duration: u64,
+    reward_token: AssetInfo,
+) -> AstroportVaultInstantiateMsg {
+    AstroportVaultInstantiateMsg {
+        owner: owner.address(),
+        vault_token_subdenom: "testVaultToken".to_string(),
+        lock_duration,
+        reward_tokens: vec![reward_token.clone().into()],
+        deposits_enabled: true,
+        performance_fee: None,
+        router: vault_dependencies
+            .cw_dex_router_robot
+            .cw_dex_router
+            .clone()
+            .into(),
+        reward_liquidation_target: reward_token.into(),
+        pool_addr,
+        liquidity_helper: LiquidityHelperUnchecked::new(
+            vault_dependencies.liquidity_helper_addr.clone(),
+        ),
+        astroport_liquidity_manager: vault_dependencies
+            .astroport_contracts
+            .liquidity_manager
+            .address
+            .clone(),
+        astroport_incentives_addr: vault_dependencies
+            .astroport_contracts
+            .incentives
+            .address
+            .clone(),
+        deposit_fee: None,
+        withdrawal_fee: None,
+    }
+}
+
+/// Sets up the vault zapper like `setup` and instantiates a second vault for
+/// the ASTRO/NTRN pool, whose base token differs from the first vault's.
+pub fn setup_with_second_vault<'a>(
+    runner: &'a TestRunner<'a>,
+    vault_lock_duration: u64,
+) -> (
+    VaultZapperRobot<'a>,
+    LockedAstroportVaultRobot<'a>,
+    SigningAccount,
+) {
+    let admin = VaultZapperRobot::default_account(runner);
+    let vault_dependencies =
+        LockedAstroportVaultRobot::instantiate_deps(runner, &admin, DEPENDENCY_ARTIFACTS_DIR);
+
+    let (axl_ntrn_vault, axl_ntrn_pool, astro_ntrn_pool) =
+        LockedAstroportVaultRobot::new_axlr_ntrn_vault(
+            runner,
+            LockedAstroportVaultRobot::contract(runner, DEPENDENCY_ARTIFACTS_DIR),
+            Coin::from_str(DENOM_CREATION_FEE).unwrap(),
+            None,
+            None,
+            None,
+            vault_lock_duration,
+            &vault_dependencies,
+            &admin,
+        );
+    let init_msg = astroport_vault_instantiate_msg(
+        &vault_dependencies,
+        &admin,
+        astro_ntrn_pool.pair_addr.to_string(),
+        vault_lock_duration,
+        AssetInfo::native("uastro"),
+    );
+    let astro_ntrn_vault = LockedAstroportVaultRobot::new_with_instantiate_msg(
+        runner,
+        LockedAstroportVaultRobot::contract(runner, DEPENDENCY_ARTIFACTS_DIR),
+        Coin::from_str(DENOM_CREATION_FEE).unwrap(),
+        &init_msg,
+        &vault_dependencies,
+        &admin,
+    );
+
+    let deps = VaultZapperDependencies {
+        astroport_contracts: vault_dependencies.astroport_contracts,
+        cw_dex_router_robot: vault_dependencies.cw_dex_router_robot,
+        liquidity_helper_addr: vault_dependencies.liquidity_helper_addr,
+        vault_robot: VaultRobot::Astroport(axl_ntrn_vault),
+        pool_assets: axl_ntrn_pool.pool_assets.clone(),
+        vault_pool: Pool::Astroport(axl_ntrn_pool),
+    };
+    let robot = VaultZapperRobot::instantiate(runner, deps, UNOPTIMIZED_PATH, &admin);
+
+    (robot, astro_ntrn_vault, admin)
+}
@@ -4,7 +4,6 @@ use std::str::FromStr;
 use super::DENOM_CREATION_FEE;
 use apollo_cw_asset::{Asset, AssetInfo, AssetList, AssetListUnchecked};
 use apollo_utils::assets::separate_natives_and_cw20s;
-use cosmwasm_schema::cw_serde;
 use cosmwasm_std::testing::mock_dependencies;
 use cosmwasm_std::{assert_approx_eq, coin, Addr, Api, Coin, Coins, Decimal, Uint128};
 use cw_dex_router::helpers::CwDexRouterUnchecked;
@@ -22,7 +21,10 @@ use liquidity_helper::LiquidityHelperUnchecked;
 use locked_astroport_vault::state::FeeConfig;
 use locked_astroport_vault_test_helpers::robot::LockedAstroportVaultRobot;
 use locked_astroport_vault_test_helpers::router::CwDexRouterRobot;
-use vault_zapper::msg::{ExecuteMsg, InstantiateMsg, Pool, QueryMsg, ReceiveChoice};
+use vault_zapper::msg::{
+    AnnotatedUnlockingPositionsResponse, ConfigResponse, ConfigUpdates, Cw20HookMsg, ExecuteMsg,
+    InstantiateMsg, Pool, QueryMsg, ReceiveChoice, SimulateDepositResponse,
+};
 
 #[cfg(feature = "osmosis-test-tube")]
 use cw_it::Artifact;
@@ -31,11 +33,6 @@ pub const VAULT_ZAPPER_WASM_NAME: &str = "vault_zapper.wasm";
 pub const ASTROPORT_ARTIFACTS_DIR: &str = "astroport-artifacts";
 pub const ASTROPORT_LIQUIDITY_HELPER_WASM_NAME: &str = "astroport_liquidity_helper.wasm";
 
-#[cw_serde]
-struct AstroportLiquidityHelperInstantiateMsg {
-    astroport_factory: String,
-}
-
 /// The default coins to fund new accounts with
 pub const DEFAULT_COINS: &str =
     "1000000000000000000uosmo,1000000000000000000untrn,1000000000000000000uaxl,1000000000000000000uastro,1000000000000000000ueth,1000000000000000000uwsteth,1000000000000000000uusdc";
@@ -180,6 +177,7 @@ impl<'a> VaultZapperRobot<'a> {
         admin: &SigningAccount,
     ) -> Self {
         let instantiate_msg = InstantiateMsg {
+            owner: admin.address(),
             router: CwDexRouterUnchecked::new(
                 dependencies
                     .cw_dex_router_robot
@@ -196,6 +194,7 @@ impl<'a> VaultZapperRobot<'a> {
                 .liquidity_manager
                 .address
                 .clone(),
+            fee_config: None,
         };
 
         // Upload contract
@@ -231,6 +230,30 @@ impl<'a> VaultZapperRobot<'a> {
         min_out: Uint128,
         unwrap_choice: Unwrap,
         signer: &SigningAccount,
+    ) -> &Self {
+        self.zapper_deposit_to_vault_with_max_slippage(
+            assets,
+            recipient,
+            vault_addr,
+            min_out,
+            None,
+            unwrap_choice,
+            signer,
+        )
+    }
+
+    /// Deposit assets into the specified vault via the vault zapper with an
+    /// optional max slippage
+    #[allow(clippy::too_many_arguments)]
+    pub fn zapper_deposit_to_vault_with_max_slippage(
+        &self,
+        assets: AssetList,
+        recipient: Option<String>,
+        vault_addr: &str,
+        min_out: Uint128,
+        max_slippage: Option<Decimal>,
+        unwrap_choice: Unwrap,
+        signer: &SigningAccount,
     ) -> &Self {
         // Increase allowance for Cw20s
         let (funds, cw20s) = separate_natives_and_cw20s(&assets);
@@ -250,6 +273,9 @@ impl<'a> VaultZapperRobot<'a> {
                 vault_address: vault_addr.to_string(),
                 recipient,
                 min_out,
+                max_slippage,
+                referral: None,
+                leg_min_outs: None,
             },
             &funds,
             signer,
@@ -296,6 +322,8 @@ impl<'a> VaultZapperRobot<'a> {
                 recipient,
                 receive_choice,
                 min_out,
+                referral: None,
+                leg_min_outs: None,
             },
             &[coin(amount.into(), self.deps.vault_robot.vault_token())],
             signer,
@@ -364,6 +392,8 @@ impl<'a> VaultZapperRobot<'a> {
                 recipient,
                 receive_choice,
                 min_out,
+                referral: None,
+                leg_min_outs: None,
             },
             &funds,
             signer,
@@ -452,6 +482,45 @@ impl<'a> VaultZapperRobot<'a> {
                 recipient,
                 receive_choice,
                 min_out,
+                referral: None,
+                leg_min_outs: None,
+            },
+            &[],
+            signer,
+        ));
+        self
+    }
+
+    /// Executes the given message on the vault zapper
+    pub fn zapper_execute(
+        &self,
+        msg: &ExecuteMsg,
+        funds: &[Coin],
+        unwrap_choice: Unwrap,
+        signer: &SigningAccount,
+    ) -> &Self {
+        unwrap_choice.unwrap(
+            self.wasm()
+                .execute(&self.vault_zapper_addr, msg, funds, signer),
+        );
+        self
+    }
+
+    /// Sends CW20 tokens to the vault zapper with the given hook message
+    pub fn zapper_cw20_send(
+        &self,
+        cw20_addr: &str,
+        amount: impl Into<Uint128>,
+        hook_msg: &Cw20HookMsg,
+        unwrap_choice: Unwrap,
+        signer: &SigningAccount,
+    ) -> &Self {
+        unwrap_choice.unwrap(self.wasm().execute(
+            cw20_addr,
+            &cw20::Cw20ExecuteMsg::Send {
+                contract: self.vault_zapper_addr.clone(),
+                amount: amount.into(),
+                msg: cosmwasm_std::to_json_binary(hook_msg).unwrap(),
             },
             &[],
             signer,
@@ -459,6 +528,58 @@ impl<'a> VaultZapperRobot<'a> {
         self
     }
 
+    /// Updates the config of the vault zapper
+    pub fn zapper_update_config(
+        &self,
+        updates: ConfigUpdates,
+        unwrap_choice: Unwrap,
+        signer: &SigningAccount,
+    ) -> &Self {
+        unwrap_choice.unwrap(self.wasm().execute(
+            &self.vault_zapper_addr,
+            &ExecuteMsg::UpdateConfig { updates },
+            &[],
+            signer,
+        ));
+        self
+    }
+
+    /// Sweeps assets held by the vault zapper to the recipient
+    pub fn zapper_sweep(
+        &self,
+        assets: AssetList,
+        recipient: Option<String>,
+        unwrap_choice: Unwrap,
+        signer: &SigningAccount,
+    ) -> &Self {
+        unwrap_choice.unwrap(self.wasm().execute(
+            &self.vault_zapper_addr,
+            &ExecuteMsg::Sweep {
+                assets: assets.into(),
+                recipient,
+            },
+            &[],
+            signer,
+        ));
+        self
+    }
+
+    /// Updates the ownership of the vault zapper
+    pub fn zapper_update_ownership(
+        &self,
+        action: cw_ownable::Action,
+        unwrap_choice: Unwrap,
+        signer: &SigningAccount,
+    ) -> &Self {
+        unwrap_choice.unwrap(self.wasm().execute(
+            &self.vault_zapper_addr,
+            &ExecuteMsg::UpdateOwnership(action),
+            &[],
+            signer,
+        ));
+        self
+    }
+
     /// Increases the test runner's block time by the given number of seconds
     pub fn increase_time(&self, seconds: u64) -> &Self {
         self.runner.increase_time(seconds).unwrap();
@@ -530,6 +651,105 @@ impl<'a> VaultZapperRobot<'a> {
             .unwrap()
     }
 
+    /// Queries the unlocking positions for a user across all vaults, annotated
+    /// with their status at the current block
+    pub fn zapper_query_annotated_user_unlocking_positions(
+        &self,
+        owner: &str,
+        start_after_vault_addr: Option<String>,
+        start_after_id: Option<u64>,
+        limit: Option<u32>,
+        claimable_only: Option<bool>,
+    ) -> AnnotatedUnlockingPositionsResponse {
+        self.wasm()
+            .query(
+                &self.vault_zapper_addr,
+                &QueryMsg::AnnotatedUserUnlockingPositions {
+                    owner: owner.to_string(),
+                    start_after_vault_addr,
+                    start_after_id,
+                    limit,
+                    claimable_only,
+                },
+            )
+            .unwrap()
+    }
+
+    /// Queries the config of the vault zapper
+    pub fn zapper_query_config(&self) -> ConfigResponse {
+        self.wasm()
+            .query(&self.vault_zapper_addr, &QueryMsg::Config {})
+            .unwrap()
+    }
+
+    /// Queries the ownership of the vault zapper
+    pub fn zapper_query_ownership(&self) -> cw_ownable::Ownership<String> {
+        self.wasm()
+            .query(&self.vault_zapper_addr, &QueryMsg::Ownership {})
+            .unwrap()
+    }
+
+    /// Queries the protocol fee config of the vault zapper
+    pub fn zapper_query_fee_config(&self) -> Option<vault_zapper::fees::FeeConfig<Addr>> {
+        self.wasm()
+            .query(&self.vault_zapper_addr, &QueryMsg::FeeConfig {})
+            .unwrap()
+    }
+
+    /// Queries the price oracle config of the vault zapper
+    pub fn zapper_query_oracle_config(&self) -> Option<vault_zapper::oracle::OracleConfig<Addr>> {
+        self.wasm()
+            .query(&self.vault_zapper_addr, &QueryMsg::OracleConfig {})
+            .unwrap()
+    }
+
+    /// Queries the vault zapper's balances of the given assets
+    pub fn zapper_query_contract_balances(&self, asset_infos: Vec<AssetInfo>) -> AssetList {
+        self.wasm()
+            .query(
+                &self.vault_zapper_addr,
+                &QueryMsg::ContractBalances {
+                    asset_infos: asset_infos.into_iter().map(Into::into).collect(),
+                },
+            )
+            .unwrap()
+    }
+
+    /// Queries the estimated outcome of depositing the given assets into the
+    /// vault via the vault zapper
+    pub fn zapper_query_simulate_deposit(&self, assets: AssetList) -> SimulateDepositResponse {
+        self.wasm()
+            .query(
+                &self.vault_zapper_addr,
+                &QueryMsg::SimulateDeposit {
+                    assets: assets.into(),
+                    vault_address: self.vault_addr(),
+                    referral: None,
+                },
+            )
+            .unwrap()
+    }
+
+    /// Queries the estimated assets received when redeeming the given amount of
+    /// vault tokens from the vault via the vault zapper
+    pub fn zapper_query_simulate_redeem(
+        &self,
+        vault_token_amount: impl Into<Uint128>,
+        receive_choice: ReceiveChoice,
+    ) -> AssetList {
+        self.wasm()
+            .query(
+                &self.vault_zapper_addr,
+                &QueryMsg::SimulateRedeem {
+                    vault_address: self.vault_addr(),
+                    vault_token_amount: vault_token_amount.into(),
+                    receive_choice,
+                    referral: None,
+                },
+            )
+            .unwrap()
+    }
+
     /// Asserts that the balance of an Astroport AssetInfo for the given address
     /// is approximately equal to the expected amount, with the given max
     /// relative difference as a string percentage.
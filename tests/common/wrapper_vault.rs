@@ -0,0 +1,176 @@
+//! A minimal cw-vault-standard vault that wraps a native token 1:1 into a
+//! token factory vault token. Used to test vaults whose base token is another
+//! vault's vault token, and vaults that call back into the zapper on redeem.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    coin, to_json_binary, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo,
+    Response, StdError, StdResult,
+};
+use cw_it::cw_multi_test::ContractWrapper;
+use cw_it::osmosis_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
+use cw_it::osmosis_std::types::osmosis::tokenfactory::v1beta1::{MsgBurn, MsgCreateDenom, MsgMint};
+use cw_it::test_tube::{Module, SigningAccount, Wasm};
+use cw_it::traits::CwItRunner;
+use cw_it::{ContractType, TestRunner};
+use cw_storage_plus::Item;
+use cw_utils::must_pay;
+use cw_vault_standard::{VaultInfoResponse, VaultStandardExecuteMsg, VaultStandardQueryMsg};
+
+const SUBDENOM: &str = "wrappedVaultToken";
+const BASE_TOKEN: Item<String> = Item::new("base_token");
+const ON_REDEEM: Item<Vec<CosmosMsg>> = Item::new("on_redeem");
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    /// The native denom to wrap
+    pub base_token: String,
+    /// Messages the vault executes after each redemption
+    pub on_redeem: Vec<CosmosMsg>,
+}
+
+fn vault_token(env: &Env) -> String {
+    format!("factory/{}/{}", env.contract.address, SUBDENOM)
+}
+
+fn instantiate(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> StdResult<Response> {
+    BASE_TOKEN.save(deps.storage, &msg.base_token)?;
+    ON_REDEEM.save(deps.storage, &msg.on_redeem)?;
+    Ok(Response::new().add_message(MsgCreateDenom {
+        sender: env.contract.address.to_string(),
+        subdenom: SUBDENOM.to_string(),
+    }))
+}
+
+fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: VaultStandardExecuteMsg<Empty>,
+) -> StdResult<Response> {
+    let base_token = BASE_TOKEN.load(deps.storage)?;
+    let vault_token = vault_token(&env);
+    let contract = env.contract.address.to_string();
+
+    // Vault tokens are minted and burned 1:1 with the base token
+    let (recipient, msgs): (Option<String>, Vec<CosmosMsg>) = match msg {
+        VaultStandardExecuteMsg::Deposit { amount, recipient } => {
+            if must_pay(&info, &base_token).map_err(|e| StdError::generic_err(e.to_string()))?
+                != amount
+            {
+                return Err(StdError::generic_err("Invalid deposit amount"));
+            }
+            let recipient = recipient.unwrap_or(info.sender.to_string());
+            let msgs = vec![
+                MsgMint {
+                    sender: contract.clone(),
+                    amount: Some(ProtoCoin {
+                        denom: vault_token.clone(),
+                        amount: amount.to_string(),
+                    }),
+                    mint_to_address: contract,
+                }
+                .into(),
+                BankMsg::Send {
+                    to_address: recipient.clone(),
+                    amount: vec![coin(amount.u128(), vault_token)],
+                }
+                .into(),
+            ];
+            (Some(recipient), msgs)
+        }
+        VaultStandardExecuteMsg::Redeem { recipient, amount } => {
+            if must_pay(&info, &vault_token).map_err(|e| StdError::generic_err(e.to_string()))?
+                != amount
+            {
+                return Err(StdError::generic_err("Invalid redeem amount"));
+            }
+            let recipient = recipient.unwrap_or(info.sender.to_string());
+            let mut msgs: Vec<CosmosMsg> = vec![
+                MsgBurn {
+                    sender: contract.clone(),
+                    amount: Some(ProtoCoin {
+                        denom: vault_token,
+                        amount: amount.to_string(),
+                    }),
+                    burn_from_address: contract,
+                }
+                .into(),
+                BankMsg::Send {
+                    to_address: recipient.clone(),
+                    amount: vec![coin(amount.u128(), base_token)],
+                }
+                .into(),
+            ];
+            msgs.extend(ON_REDEEM.load(deps.storage)?);
+            (Some(recipient), msgs)
+        }
+        _ => return Err(StdError::generic_err("Unsupported message")),
+    };
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("recipient", recipient.unwrap_or_default()))
+}
+
+fn query(deps: Deps, env: Env, msg: VaultStandardQueryMsg<Empty>) -> StdResult<Binary> {
+    match msg {
+        VaultStandardQueryMsg::Info {} => to_json_binary(&VaultInfoResponse {
+            base_token: BASE_TOKEN.load(deps.storage)?,
+            vault_token: vault_token(&env),
+        }),
+        VaultStandardQueryMsg::ConvertToShares { amount }
+        | VaultStandardQueryMsg::ConvertToAssets { amount } => to_json_binary(&amount),
+        _ => Err(StdError::generic_err("Unsupported query")),
+    }
+}
+
+/// Uploads and instantiates a wrapper vault for `base_token` and returns its
+/// address and vault token. The signer pays the token factory denom creation
+/// fee.
+pub fn instantiate_wrapper_vault(
+    runner: &TestRunner,
+    base_token: &str,
+    signer: &SigningAccount,
+) -> (String, String) {
+    instantiate_reentrant_wrapper_vault(runner, base_token, vec![], signer)
+}
+
+/// Like `instantiate_wrapper_vault`, but the vault executes `on_redeem` after
+/// each redemption, e.g. to call back into the zapper that redeems from it.
+pub fn instantiate_reentrant_wrapper_vault(
+    runner: &TestRunner,
+    base_token: &str,
+    on_redeem: Vec<CosmosMsg>,
+    signer: &SigningAccount,
+) -> (String, String) {
+    let contract = ContractType::MultiTestContract(Box::new(ContractWrapper::new_with_empty(
+        execute,
+        instantiate,
+        query,
+    )));
+    let code_id = runner.store_code(contract, signer).unwrap();
+    let addr = Wasm::new(runner)
+        .instantiate(
+            code_id,
+            &InstantiateMsg {
+                base_token: base_token.to_string(),
+                on_redeem,
+            },
+            None,
+            Some("Wrapper Vault"),
+            &[std::str::FromStr::from_str(super::DENOM_CREATION_FEE).unwrap()],
+            signer,
+        )
+        .unwrap()
+        .data
+        .address;
+    let vault_token = format!("factory/{}/{}", addr, SUBDENOM);
+
+    (addr, vault_token)
+}
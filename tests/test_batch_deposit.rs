@@ -0,0 +1,117 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::setup_with_second_vault;
+use cosmwasm_std::{assert_approx_eq, coin, Decimal, Uint128};
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::{Account, Module, Wasm};
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use test_case::test_case;
+use vault_zapper::msg::{BatchDepositEntry, ExecuteMsg, QueryMsg, SimulateDepositResponse};
+
+pub mod common;
+
+#[test_case("uaxl"; "asset of the first vault's pool")]
+#[test_case("uastro"; "asset of the second vault's pool")]
+#[test_case("untrn"; "asset of both pools")]
+fn batch_deposit_works(denom: &str) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, 0);
+    let wasm = Wasm::new(&runner);
+
+    let amount = 1_000_000u128;
+    let weights = [Decimal::percent(60), Decimal::percent(40)];
+    let vaults = [robot.vault_addr(), second_vault.vault_addr()];
+
+    // Simulate depositing each vault's share separately
+    let simulated: Vec<Uint128> = vaults
+        .iter()
+        .zip(weights)
+        .map(|(vault, weight)| {
+            let res: SimulateDepositResponse = wasm
+                .query(
+                    &robot.vault_zapper_addr,
+                    &QueryMsg::SimulateDeposit {
+                        assets: AssetList::from(vec![Asset::new(
+                            AssetInfo::native(denom),
+                            Uint128::new(amount) * weight,
+                        )])
+                        .into(),
+                        vault_address: vault.clone(),
+                        referral: None,
+                    },
+                )
+                .unwrap();
+            res.vault_token_amount
+        })
+        .collect();
+
+    let batch_msg = |min_out: Uint128| ExecuteMsg::BatchDeposit {
+        deposits: vaults
+            .iter()
+            .zip(weights)
+            .map(|(vault, weight)| BatchDepositEntry {
+                vault_address: vault.clone(),
+                weight,
+                min_out,
+            })
+            .collect(),
+        assets: AssetList::from(vec![Asset::new(AssetInfo::native(denom), amount)]).into(),
+        recipient: None,
+    };
+    let funds = [coin(amount, denom)];
+
+    robot
+        .zapper_execute(
+            &batch_msg(Uint128::MAX),
+            &funds,
+            Unwrap::Err("Minimum amount not met"),
+            &admin,
+        )
+        .zapper_execute(&batch_msg(Uint128::one()), &funds, Unwrap::Ok, &admin);
+
+    let balances = [
+        robot.query_vault_token_balance(admin.address()),
+        second_vault.query_vault_token_balance(admin.address()),
+    ];
+    for (balance, simulated) in balances.into_iter().zip(simulated) {
+        assert_approx_eq!(balance, simulated, "0.01");
+    }
+
+    // Nothing should be left reserved or held by the zapper
+    robot.assert_native_token_balance_eq(&robot.vault_zapper_addr, denom, 0u128);
+}
+
+#[test_case(vec![], "no deposits"; "no deposits")]
+#[test_case(vec![(0, 50), (1, 40)], "weights must sum to 1"; "weights less than 1")]
+#[test_case(vec![(0, 100), (1, 0)], "weights must be positive"; "zero weight")]
+#[test_case(vec![(0, 50), (0, 50)], "duplicate vault"; "duplicate vault")]
+fn batch_deposit_with_invalid_deposits_fails(
+    deposits: Vec<(usize, u64)>,
+    expected_err: &'static str,
+) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, 0);
+    let vaults = [robot.vault_addr(), second_vault.vault_addr()];
+
+    robot.zapper_execute(
+        &ExecuteMsg::BatchDeposit {
+            deposits: deposits
+                .into_iter()
+                .map(|(i, weight)| BatchDepositEntry {
+                    vault_address: vaults[i].clone(),
+                    weight: Decimal::percent(weight),
+                    min_out: Uint128::one(),
+                })
+                .collect(),
+            assets: AssetList::from(vec![Asset::new(AssetInfo::native("uaxl"), 1_000_000u128)])
+                .into(),
+            recipient: None,
+        },
+        &[coin(1_000_000, "uaxl")],
+        Unwrap::Err(expected_err),
+        &admin,
+    );
+}
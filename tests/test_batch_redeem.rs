@@ -0,0 +1,150 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::setup_with_second_vault;
+use cosmwasm_std::{assert_approx_eq, coin, Coin, Uint128};
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::{Account, Module, Wasm};
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::{ExecuteMsg, QueryMsg, ReceiveChoice};
+
+pub mod common;
+
+#[test]
+fn batch_redeem_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, 0);
+    let wasm = Wasm::new(&runner);
+
+    // Deposit into both vaults
+    robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_deposit_to_vault(
+            vec![Asset::new(
+                AssetInfo::native("uastro"),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            &second_vault.vault_addr(),
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        );
+    let vaults = [robot.vault_addr(), second_vault.vault_addr()];
+    let mut funds: Vec<Coin> = vec![
+        coin(
+            robot.query_vault_token_balance(admin.address()).u128(),
+            robot.deps.vault_robot.vault_token(),
+        ),
+        coin(
+            second_vault
+                .query_vault_token_balance(admin.address())
+                .u128(),
+            second_vault.vault_token(),
+        ),
+    ];
+    funds.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    // Simulate redeeming from each vault separately
+    let receive_choice = ReceiveChoice::SwapTo(AssetInfo::native("untrn"));
+    let simulated: Uint128 = vaults
+        .iter()
+        .zip([
+            robot.query_vault_token_balance(admin.address()),
+            second_vault.query_vault_token_balance(admin.address()),
+        ])
+        .map(|(vault, amount)| {
+            let res: AssetList = wasm
+                .query(
+                    &robot.vault_zapper_addr,
+                    &QueryMsg::SimulateRedeem {
+                        vault_address: vault.clone(),
+                        vault_token_amount: amount,
+                        receive_choice: receive_choice.clone(),
+                        referral: None,
+                    },
+                )
+                .unwrap();
+            res.find(&AssetInfo::native("untrn")).unwrap().amount
+        })
+        .sum();
+
+    let batch_msg = |min_out: Uint128| ExecuteMsg::BatchRedeem {
+        redemptions: vaults.to_vec(),
+        recipient: None,
+        receive_choice: receive_choice.clone(),
+        min_out: AssetList::from(vec![Asset::new(AssetInfo::native("untrn"), min_out)]).into(),
+    };
+
+    // The minimum amount is enforced on the combined assets
+    let balance_before = robot.query_native_token_balance(admin.address(), "untrn");
+    robot
+        .zapper_execute(
+            &batch_msg(simulated * Uint128::new(2)),
+            &funds,
+            Unwrap::Err("Minimum amount not met"),
+            &admin,
+        )
+        .zapper_execute(&batch_msg(Uint128::one()), &funds, Unwrap::Ok, &admin)
+        .assert_vault_token_balance_eq(admin.address(), 0u128);
+    second_vault.assert_vault_token_balance_eq(admin.address(), 0u128);
+    let received = robot.query_native_token_balance(admin.address(), "untrn") - balance_before;
+    assert_approx_eq!(received, simulated, "0.01");
+}
+
+#[test]
+fn batch_redeem_with_invalid_funds_fails() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, 0);
+
+    let vault_tokens = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    let funds = [coin(
+        vault_tokens.u128(),
+        robot.deps.vault_robot.vault_token(),
+    )];
+    let batch_msg = |redemptions: Vec<String>| ExecuteMsg::BatchRedeem {
+        redemptions,
+        recipient: None,
+        receive_choice: ReceiveChoice::Underlying,
+        min_out: AssetList::new().into(),
+    };
+
+    robot
+        // The vault tokens of every vault must be sent
+        .zapper_execute(
+            &batch_msg(vec![robot.vault_addr(), second_vault.vault_addr()]),
+            &funds,
+            Unwrap::Err("Invalid vault token sent"),
+            &admin,
+        )
+        // The sent vault tokens must be of the vaults redeemed from
+        .zapper_execute(
+            &batch_msg(vec![second_vault.vault_addr()]),
+            &funds,
+            Unwrap::Err("Invalid vault token sent"),
+            &admin,
+        )
+        .zapper_execute(
+            &batch_msg(vec![]),
+            &[],
+            Unwrap::Err("no redemptions"),
+            &admin,
+        );
+}
@@ -0,0 +1,94 @@
+use common::setup;
+use cw_dex_router::helpers::CwDexRouterUnchecked;
+use cw_it::helpers::Unwrap;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use liquidity_helper::LiquidityHelperUnchecked;
+use vault_zapper::msg::ConfigUpdates;
+
+pub mod common;
+
+#[test]
+fn update_config_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let user = common::VaultZapperRobot::default_account(&runner);
+
+    let config = robot.zapper_query_config();
+    assert_eq!(
+        config.router.addr().to_string(),
+        robot
+            .deps
+            .cw_dex_router_robot
+            .cw_dex_router
+            .addr()
+            .to_string()
+    );
+    assert_eq!(
+        config.liquidity_helper.addr().to_string(),
+        robot.deps.liquidity_helper_addr
+    );
+
+    let new_router = user.address();
+    let new_liquidity_helper = admin.address();
+    let updates = ConfigUpdates {
+        router: Some(CwDexRouterUnchecked::new(new_router.clone())),
+        liquidity_helper: Some(LiquidityHelperUnchecked::new(new_liquidity_helper.clone())),
+        ..Default::default()
+    };
+
+    // Only the owner can update the config
+    robot
+        .zapper_update_config(
+            updates.clone(),
+            Unwrap::Err("Caller is not the contract's current owner"),
+            &user,
+        )
+        .zapper_update_config(updates, Unwrap::Ok, &admin);
+
+    let config = robot.zapper_query_config();
+    assert_eq!(config.router.addr().to_string(), new_router);
+    assert_eq!(
+        config.liquidity_helper.addr().to_string(),
+        new_liquidity_helper
+    );
+}
+
+#[test]
+fn two_step_ownership_transfer_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let new_owner = common::VaultZapperRobot::default_account(&runner);
+
+    assert_eq!(robot.zapper_query_ownership().owner, Some(admin.address()));
+
+    robot
+        .zapper_update_ownership(
+            cw_ownable::Action::TransferOwnership {
+                new_owner: new_owner.address(),
+                expiry: None,
+            },
+            Unwrap::Ok,
+            &admin,
+        )
+        // Ownership is not transferred until the new owner accepts it
+        .zapper_update_config(
+            ConfigUpdates::default(),
+            Unwrap::Err("Caller is not the contract's current owner"),
+            &new_owner,
+        )
+        .zapper_update_ownership(cw_ownable::Action::AcceptOwnership, Unwrap::Ok, &new_owner)
+        .zapper_update_config(ConfigUpdates::default(), Unwrap::Ok, &new_owner)
+        .zapper_update_config(
+            ConfigUpdates::default(),
+            Unwrap::Err("Caller is not the contract's current owner"),
+            &admin,
+        );
+
+    assert_eq!(
+        robot.zapper_query_ownership().owner,
+        Some(new_owner.address())
+    );
+}
@@ -0,0 +1,80 @@
+use apollo_cw_asset::{Asset, AssetInfo};
+use common::cw20_vault::instantiate_cw20_vault;
+use cosmwasm_std::{Addr, Uint128};
+use cw_it::astroport::robot::AstroportTestRobot;
+use cw_it::helpers::Unwrap;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::Cw20HookMsg;
+
+pub mod common;
+
+#[test]
+fn deposit_into_and_unlock_from_cw20_vault() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = common::setup(&runner, 0);
+    let inner_vault_token = robot.deps.vault_robot.vault_token();
+    let cw20_vault = instantiate_cw20_vault(&runner, &inner_vault_token, &admin);
+    let cw20_vault_token = AssetInfo::cw20(Addr::unchecked(&cw20_vault));
+
+    let amount = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+
+    // The minimum amount is enforced on the recipient's CW20 vault token balance
+    let assets = vec![Asset::native(&inner_vault_token, amount)].into();
+    robot
+        .zapper_deposit_to_vault(
+            assets,
+            None,
+            &cw20_vault,
+            amount + Uint128::one(),
+            Unwrap::Err("Minimum amount not met"),
+            &admin,
+        )
+        .zapper_deposit_to_vault(
+            vec![Asset::native(&inner_vault_token, amount)].into(),
+            None,
+            &cw20_vault,
+            amount,
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_asset_balance_eq(&cw20_vault_token.clone().into(), &admin.address(), amount);
+
+    // The CW20 vault tokens can only be unlocked from their own vault
+    robot
+        .zapper_cw20_send(
+            &cw20_vault,
+            amount,
+            &Cw20HookMsg::Unlock {
+                vault_address: robot.vault_addr(),
+            },
+            Unwrap::Err("Invalid vault token sent"),
+            &admin,
+        )
+        .zapper_cw20_send(
+            &cw20_vault,
+            amount,
+            &Cw20HookMsg::Unlock {
+                vault_address: cw20_vault.clone(),
+            },
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_asset_balance_eq(&cw20_vault_token.clone().into(), &admin.address(), 0u128)
+        .assert_asset_balance_eq(&cw20_vault_token.into(), &robot.vault_zapper_addr, 0u128);
+
+    // The unlocking position is recorded for the sender of the vault tokens
+    let positions = robot.zapper_query_user_unlocking_positions(&admin.address(), None, None, None);
+    let position = &positions[&Addr::unchecked(&cw20_vault)][0];
+    assert_eq!(position.base_token_amount, amount);
+}
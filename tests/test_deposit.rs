@@ -1,11 +1,14 @@
 use apollo_cw_asset::{Asset, AssetInfo};
 use common::setup;
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Decimal, Uint128};
 use cw_it::astroport::robot::AstroportTestRobot;
 use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
 use cw_it::test_tube::Account;
 use cw_it::OwnedTestRunner;
 use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use test_case::test_case;
+use vault_zapper::msg::Cw20HookMsg;
 
 pub mod common;
 
@@ -120,6 +123,117 @@ fn deposit_asset_not_in_pool() {
         .assert_asset_balance_eq(&asset.into(), &admin.address(), balance - deposit_amount);
 }
 
+#[test]
+fn deposit_leaves_other_balances_of_zapper_untouched() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    // Balances the zapper already held before the deposit are not refunded
+    let asset = AssetInfo::native("uastro");
+    let dust = Uint128::new(1000);
+    robot.send_native_tokens(&admin, &robot.vault_zapper_addr, dust, "uastro");
+    let balance = robot.query_asset_balance(&asset.clone().into(), &admin.address());
+    let deposit_amount = Uint128::new(1000000);
+
+    robot
+        .zapper_deposit(
+            vec![Asset::new(asset.clone(), deposit_amount)].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_gt(admin.address(), 0u128)
+        .assert_asset_balance_eq(
+            &asset.clone().into(),
+            &admin.address(),
+            balance - deposit_amount,
+        )
+        .assert_asset_balance_eq(&asset.into(), &robot.vault_zapper_addr, dust);
+    for pool_asset in &robot.deps.pool_assets {
+        robot.assert_asset_balance_eq(&pool_asset.clone().into(), &robot.vault_zapper_addr, 0u128);
+    }
+}
+
+#[test]
+fn deposit_does_not_use_balances_already_held_by_zapper() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    // Balances of pool assets the zapper already held are not provided as
+    // liquidity along with the deposited asset
+    let held_asset = robot.deps.pool_assets[1].clone();
+    let dust = Uint128::new(1000);
+    let denom = match &held_asset {
+        AssetInfo::Native(denom) => denom.clone(),
+        AssetInfo::Cw20(_) => panic!("Pool asset is not native"),
+    };
+    robot.send_native_tokens(&admin, &robot.vault_zapper_addr, dust, denom);
+
+    robot
+        .zapper_deposit(
+            vec![Asset::new(
+                robot.deps.pool_assets[0].clone(),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_gt(admin.address(), 0u128)
+        .assert_asset_balance_eq(&held_asset.into(), &robot.vault_zapper_addr, dust)
+        .assert_asset_balance_eq(
+            &robot.deps.pool_assets[0].clone().into(),
+            &robot.vault_zapper_addr,
+            0u128,
+        );
+}
+
+#[test]
+fn deposit_lp_token_via_cw20_hook_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    // The LP token of the vault is a CW20, so it can be sent with a hook msg
+    let lp_token_addr = match robot.deps.vault_pool.lp_token() {
+        AssetInfo::Cw20(addr) => addr.to_string(),
+        AssetInfo::Native(_) => panic!("LP token is not a CW20"),
+    };
+    let balance = robot.query_base_token_balance(admin.address());
+    let deposit_amount = Uint128::new(1000000);
+    let hook_msg = |min_out: Uint128| Cw20HookMsg::Deposit {
+        vault_address: robot.vault_addr(),
+        recipient: None,
+        min_out,
+        max_slippage: None,
+        referral: None,
+        leg_min_outs: None,
+    };
+
+    robot
+        .zapper_cw20_send(
+            &lp_token_addr,
+            deposit_amount,
+            &hook_msg(deposit_amount * Uint128::new(1_000_000_000_000)),
+            Unwrap::Err("Minimum amount not met"),
+            &admin,
+        )
+        .zapper_cw20_send(
+            &lp_token_addr,
+            deposit_amount,
+            &hook_msg(Uint128::one()),
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_gt(admin.address(), 0u128)
+        .assert_base_token_balance_eq(admin.address(), balance - deposit_amount);
+}
+
 #[test]
 fn deposit_lp_min_out_respected() {
     let owned_runner: OwnedTestRunner = common::get_test_runner();
@@ -179,3 +293,48 @@ fn deposit_one_asset_of_pool_min_out_respected() {
         .assert_vault_token_balance_gt(admin.address(), 0u128)
         .assert_asset_balance_eq(&asset.into(), &admin.address(), balance - deposit_amount);
 }
+
+#[test_case(None, Decimal::zero(); "lp token, no slippage")]
+#[test_case(Some(0), Decimal::percent(1); "one asset of pool, one percent slippage")]
+fn deposit_with_max_slippage_works(pool_asset_idx: Option<usize>, max_slippage: Decimal) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    let deposit_asset_info = match pool_asset_idx {
+        Some(idx) => robot.deps.pool_assets[idx].clone(),
+        None => robot.deps.vault_pool.lp_token(),
+    };
+    let deposit_asset = Asset::new(deposit_asset_info, Uint128::new(1000000));
+
+    robot
+        .zapper_deposit_to_vault_with_max_slippage(
+            vec![deposit_asset].into(),
+            None,
+            &robot.vault_addr(),
+            Uint128::zero(),
+            Some(max_slippage),
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_gt(admin.address(), 0u128);
+}
+
+#[test]
+fn deposit_with_invalid_max_slippage_fails() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    let deposit_asset = Asset::new(robot.deps.vault_pool.lp_token(), Uint128::new(1000000));
+
+    robot.zapper_deposit_to_vault_with_max_slippage(
+        vec![deposit_asset].into(),
+        None,
+        &robot.vault_addr(),
+        Uint128::zero(),
+        Some(Decimal::percent(101)),
+        Unwrap::Err("Max slippage must be at most 100%"),
+        &admin,
+    );
+}
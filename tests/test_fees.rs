@@ -0,0 +1,344 @@
+use apollo_cw_asset::{Asset, AssetList};
+use common::setup;
+use cosmwasm_std::{assert_approx_eq, coin, Addr, Coin, Decimal, Uint128};
+use cw_it::astroport::robot::AstroportTestRobot;
+use cw_it::helpers::Unwrap;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use test_case::test_case;
+use vault_zapper::fees::{FeeConfig, Referral};
+use vault_zapper::msg::{ConfigUpdates, ExecuteMsg, ReceiveChoice};
+
+pub mod common;
+
+fn fee_config_updates(
+    fee_rate: Decimal,
+    fee_recipient: String,
+    vault_fee_rates: Vec<(String, Decimal)>,
+) -> ConfigUpdates {
+    ConfigUpdates {
+        fee_config: Some(FeeConfig {
+            fee_rate,
+            fee_recipient,
+            vault_fee_rates,
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn update_fee_config_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let fee_recipient = common::VaultZapperRobot::default_account(&runner);
+
+    assert_eq!(robot.zapper_query_fee_config(), None);
+
+    // Only the owner can update the fee config
+    let updates = fee_config_updates(
+        Decimal::percent(1),
+        fee_recipient.address(),
+        vec![(robot.vault_addr(), Decimal::percent(2))],
+    );
+    robot
+        .zapper_update_config(
+            updates.clone(),
+            Unwrap::Err("Caller is not the contract's current owner"),
+            &fee_recipient,
+        )
+        .zapper_update_config(updates, Unwrap::Ok, &admin);
+
+    assert_eq!(
+        robot.zapper_query_fee_config(),
+        Some(FeeConfig {
+            fee_rate: Decimal::percent(1),
+            fee_recipient: Addr::unchecked(fee_recipient.address()),
+            vault_fee_rates: vec![(Addr::unchecked(robot.vault_addr()), Decimal::percent(2))],
+        })
+    );
+
+    // Fee rates of 100% or more are rejected
+    robot
+        .zapper_update_config(
+            fee_config_updates(Decimal::one(), fee_recipient.address(), vec![]),
+            Unwrap::Err("Fee rate must be less than 100%"),
+            &admin,
+        )
+        .zapper_update_config(
+            fee_config_updates(
+                Decimal::percent(1),
+                fee_recipient.address(),
+                vec![(robot.vault_addr(), Decimal::percent(100))],
+            ),
+            Unwrap::Err("Fee rate must be less than 100%"),
+            &admin,
+        );
+}
+
+#[test_case(false; "default fee rate")]
+#[test_case(true; "vault fee rate override")]
+fn deposit_takes_fee(vault_override: bool) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let fee_recipient = common::VaultZapperRobot::default_account(&runner);
+
+    let vault_fee_rates = if vault_override {
+        vec![(robot.vault_addr(), Decimal::percent(2))]
+    } else {
+        vec![]
+    };
+    robot.zapper_update_config(
+        fee_config_updates(
+            Decimal::percent(1),
+            fee_recipient.address(),
+            vault_fee_rates,
+        ),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    let deposit_asset_info = robot.deps.pool_assets[0].clone();
+    let deposit_amount = Uint128::new(1000000);
+    let fee_recipient_balance_before =
+        robot.query_asset_balance(&deposit_asset_info.clone().into(), &fee_recipient.address());
+
+    // The simulation accounts for the fee
+    let simulated = robot.zapper_query_simulate_deposit(
+        vec![Asset::new(deposit_asset_info.clone(), deposit_amount)].into(),
+    );
+
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(deposit_asset_info.clone(), deposit_amount)].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    assert_approx_eq!(vault_token_balance, simulated.vault_token_amount, "0.01");
+
+    let expected_fee = if vault_override {
+        Uint128::new(20000)
+    } else {
+        Uint128::new(10000)
+    };
+    robot.assert_asset_balance_eq(
+        &deposit_asset_info.into(),
+        &fee_recipient.address(),
+        fee_recipient_balance_before + expected_fee,
+    );
+}
+
+#[test_case(ReceiveChoice::BaseToken; "base token")]
+#[test_case(ReceiveChoice::Underlying; "underlying")]
+fn redeem_takes_fee(receive_choice: ReceiveChoice) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let fee_recipient = common::VaultZapperRobot::default_account(&runner);
+
+    // Deposit the base token before enabling the fee
+    let base_token = robot.deps.vault_pool.lp_token();
+    robot.zapper_deposit(
+        vec![Asset::new(base_token.clone(), Uint128::new(1000000))].into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    robot.zapper_update_config(
+        fee_config_updates(Decimal::percent(1), fee_recipient.address(), vec![]),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    let received_assets = match receive_choice {
+        ReceiveChoice::Underlying => robot.deps.pool_assets.clone(),
+        _ => vec![base_token],
+    };
+    let query_balances = |address: &str| -> Vec<Uint128> {
+        received_assets
+            .iter()
+            .map(|info| robot.query_asset_balance(&info.clone().into(), address))
+            .collect()
+    };
+    let user_balances_before = query_balances(&admin.address());
+    let fee_recipient_balances_before = query_balances(&fee_recipient.address());
+
+    robot
+        .zapper_redeem_all(None, receive_choice, AssetList::new(), Unwrap::Ok, &admin)
+        .assert_vault_token_balance_eq(admin.address(), 0u128);
+
+    let user_balances_after = query_balances(&admin.address());
+    let fee_recipient_balances_after = query_balances(&fee_recipient.address());
+
+    // The fee recipient should have received 1% of each of the assets
+    for i in 0..received_assets.len() {
+        let received = user_balances_after[i] - user_balances_before[i];
+        let fee = fee_recipient_balances_after[i] - fee_recipient_balances_before[i];
+        assert!(!fee.is_zero());
+        assert_approx_eq!(fee * Uint128::new(99), received, "0.001");
+    }
+}
+
+#[test]
+fn update_max_referral_fee_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    assert_eq!(robot.zapper_query_config().max_referral_fee_bps, 0);
+
+    robot
+        .zapper_update_config(
+            ConfigUpdates {
+                max_referral_fee_bps: Some(10000),
+                ..Default::default()
+            },
+            Unwrap::Err("Max referral fee must be less than 100%"),
+            &admin,
+        )
+        .zapper_update_config(
+            ConfigUpdates {
+                max_referral_fee_bps: Some(100),
+                ..Default::default()
+            },
+            Unwrap::Ok,
+            &admin,
+        );
+
+    assert_eq!(robot.zapper_query_config().max_referral_fee_bps, 100);
+}
+
+#[test]
+fn deposit_with_referral_takes_referral_fee() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let fee_recipient = common::VaultZapperRobot::default_account(&runner);
+    let referrer = common::VaultZapperRobot::default_account(&runner);
+
+    let deposit_asset_info = robot.deps.pool_assets[0].clone();
+    let deposit_amount = Uint128::new(1000000);
+    let deposit_msg = |fee_bps: u16| ExecuteMsg::Deposit {
+        assets: AssetList::from(vec![Asset::new(deposit_asset_info.clone(), deposit_amount)])
+            .into(),
+        vault_address: robot.vault_addr(),
+        recipient: None,
+        min_out: Uint128::one(),
+        max_slippage: None,
+        referral: Some(Referral {
+            address: referrer.address(),
+            fee_bps,
+        }),
+        leg_min_outs: None,
+    };
+    let funds: [Coin; 1] = [Asset::new(deposit_asset_info.clone(), deposit_amount)
+        .try_into()
+        .unwrap()];
+
+    // Referral fees are not allowed until the owner sets a maximum
+    robot.zapper_execute(
+        &deposit_msg(50),
+        &funds,
+        Unwrap::Err("Referral fee of 50 bps exceeds the maximum of 0 bps"),
+        &admin,
+    );
+
+    robot.zapper_update_config(
+        ConfigUpdates {
+            max_referral_fee_bps: Some(100),
+            ..fee_config_updates(Decimal::percent(1), fee_recipient.address(), vec![])
+        },
+        Unwrap::Ok,
+        &admin,
+    );
+
+    let query_balance =
+        |address: &str| robot.query_asset_balance(&deposit_asset_info.clone().into(), address);
+    let referrer_balance_before = query_balance(&referrer.address());
+    let fee_recipient_balance_before = query_balance(&fee_recipient.address());
+
+    robot
+        .zapper_execute(
+            &deposit_msg(200),
+            &funds,
+            Unwrap::Err("Referral fee of 200 bps exceeds the maximum of 100 bps"),
+            &admin,
+        )
+        .zapper_execute(&deposit_msg(50), &funds, Unwrap::Ok, &admin)
+        .assert_vault_token_balance_gt(admin.address(), 0u128);
+
+    // Both fees are taken from the full deposited amount
+    assert_eq!(
+        query_balance(&referrer.address()),
+        referrer_balance_before + Uint128::new(5000)
+    );
+    assert_eq!(
+        query_balance(&fee_recipient.address()),
+        fee_recipient_balance_before + Uint128::new(10000)
+    );
+}
+
+#[test]
+fn redeem_with_referral_takes_referral_fee() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let referrer = common::VaultZapperRobot::default_account(&runner);
+
+    let base_token = robot.deps.vault_pool.lp_token();
+    robot
+        .zapper_deposit(
+            vec![Asset::new(base_token.clone(), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_update_config(
+            ConfigUpdates {
+                max_referral_fee_bps: Some(100),
+                ..Default::default()
+            },
+            Unwrap::Ok,
+            &admin,
+        );
+
+    let query_balance =
+        |address: &str| robot.query_asset_balance(&base_token.clone().into(), address);
+    let user_balance_before = query_balance(&admin.address());
+    let referrer_balance_before = query_balance(&referrer.address());
+
+    let vault_token_balance = robot.query_vault_token_balance(admin.address());
+    robot.zapper_execute(
+        &ExecuteMsg::Redeem {
+            vault_address: robot.vault_addr(),
+            recipient: None,
+            receive_choice: ReceiveChoice::BaseToken,
+            min_out: AssetList::new().into(),
+            referral: Some(Referral {
+                address: referrer.address(),
+                fee_bps: 100,
+            }),
+            leg_min_outs: None,
+        },
+        &[coin(
+            vault_token_balance.u128(),
+            robot.deps.vault_robot.vault_token(),
+        )],
+        Unwrap::Ok,
+        &admin,
+    );
+
+    // The referrer should have received 1% of the redeemed base tokens
+    let received = query_balance(&admin.address()) - user_balance_before;
+    let referral_fee = query_balance(&referrer.address()) - referrer_balance_before;
+    assert!(!referral_fee.is_zero());
+    assert_approx_eq!(referral_fee * Uint128::new(99), received, "0.001");
+}
@@ -0,0 +1,172 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::{setup, setup_with_second_vault};
+use cosmwasm_std::{coin, Coin, Uint128};
+use cw_it::helpers::Unwrap;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use test_case::test_case;
+use vault_zapper::msg::{ExecuteMsg, LegMinOutsUnchecked, ReceiveChoice};
+
+pub mod common;
+
+const HUGE: Uint128 = Uint128::new(u128::MAX / 2);
+
+#[test_case(Uint128::one(), Uint128::one(), Unwrap::Ok ; "small minimums")]
+#[test_case(HUGE, Uint128::one(), Unwrap::Err("Did not receive minimum amount") ; "swap minimum not met")]
+#[test_case(Uint128::one(), HUGE, Unwrap::Err("Slippage is more than expected") ; "provide liquidity minimum not met")]
+fn deposit_with_leg_min_outs(swap_min_out: Uint128, provide_min_out: Uint128, unwrap: Unwrap) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    // Depositing an asset that is not in the pool swaps it into the first pool
+    // asset before providing liquidity
+    let asset = AssetInfo::native("uastro");
+    let deposit_amount = Uint128::new(1000000);
+    let leg_min_outs = LegMinOutsUnchecked {
+        swap: AssetList::from(vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            swap_min_out,
+        )])
+        .into(),
+        provide_liquidity: provide_min_out,
+        withdraw_liquidity: AssetList::new().into(),
+    };
+
+    let is_ok = matches!(unwrap, Unwrap::Ok);
+    robot.zapper_execute(
+        &ExecuteMsg::Deposit {
+            assets: AssetList::from(vec![Asset::new(asset.clone(), deposit_amount)]).into(),
+            vault_address: robot.vault_addr(),
+            recipient: None,
+            min_out: Uint128::one(),
+            max_slippage: None,
+            referral: None,
+            leg_min_outs: Some(leg_min_outs),
+        },
+        &[coin(deposit_amount.u128(), "uastro")],
+        unwrap,
+        &admin,
+    );
+    if is_ok {
+        robot.assert_vault_token_balance_gt(admin.address(), 0u128);
+    } else {
+        robot.assert_vault_token_balance_eq(admin.address(), 0u128);
+    }
+}
+
+#[test_case(Uint128::one(), Uint128::one(), Unwrap::Ok ; "small minimums")]
+#[test_case(Uint128::one(), HUGE, Unwrap::Err("but expected") ; "withdraw liquidity minimum not met")]
+#[test_case(HUGE, Uint128::one(), Unwrap::Err("Did not receive minimum amount") ; "swap minimum not met")]
+fn redeem_with_leg_min_outs(swap_min_out: Uint128, withdraw_min_out: Uint128, unwrap: Unwrap) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    robot.zapper_deposit(
+        vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            Uint128::new(1000000),
+        )]
+        .into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+    let vault_token_balance = robot.query_vault_token_balance(admin.address());
+
+    // Redeeming to the second pool asset withdraws liquidity and swaps the
+    // withdrawn first pool asset into the second
+    let requested_asset = robot.deps.pool_assets[1].clone();
+    let leg_min_outs = LegMinOutsUnchecked {
+        swap: AssetList::from(vec![Asset::new(requested_asset.clone(), swap_min_out)]).into(),
+        provide_liquidity: Uint128::zero(),
+        withdraw_liquidity: AssetList::from(vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            withdraw_min_out,
+        )])
+        .into(),
+    };
+    let funds: Vec<Coin> = vec![coin(
+        vault_token_balance.u128(),
+        robot.deps.vault_robot.vault_token(),
+    )];
+
+    let is_ok = matches!(unwrap, Unwrap::Ok);
+    robot.zapper_execute(
+        &ExecuteMsg::Redeem {
+            vault_address: robot.vault_addr(),
+            recipient: None,
+            receive_choice: ReceiveChoice::SwapTo(requested_asset),
+            min_out: AssetList::new().into(),
+            referral: None,
+            leg_min_outs: Some(leg_min_outs),
+        },
+        &funds,
+        unwrap,
+        &admin,
+    );
+    if is_ok {
+        robot.assert_vault_token_balance_eq(admin.address(), 0u128);
+    } else {
+        robot.assert_vault_token_balance_eq(admin.address(), vault_token_balance);
+    }
+}
+
+#[test_case(Uint128::one(), Uint128::one(), Unwrap::Ok ; "small minimums")]
+#[test_case(HUGE, Uint128::one(), Unwrap::Err("but expected") ; "withdraw liquidity minimum not met")]
+#[test_case(Uint128::one(), HUGE, Unwrap::Err("Slippage is more than expected") ; "provide liquidity minimum not met")]
+fn migrate_with_leg_min_outs(withdraw_min_out: Uint128, provide_min_out: Uint128, unwrap: Unwrap) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, to_vault, admin) = setup_with_second_vault(&runner, 0);
+
+    robot.zapper_deposit(
+        vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            Uint128::new(1000000),
+        )]
+        .into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+    let vault_token_balance = robot.query_vault_token_balance(admin.address());
+
+    // Migrating to the ASTRO/NTRN vault withdraws liquidity from the AXL/NTRN
+    // pool and provides liquidity to the ASTRO/NTRN pool
+    let leg_min_outs = LegMinOutsUnchecked {
+        swap: AssetList::new().into(),
+        provide_liquidity: provide_min_out,
+        withdraw_liquidity: AssetList::from(vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            withdraw_min_out,
+        )])
+        .into(),
+    };
+
+    let is_ok = matches!(unwrap, Unwrap::Ok);
+    robot.zapper_execute(
+        &ExecuteMsg::MigrateBetweenVaults {
+            from_vault: robot.vault_addr(),
+            to_vault: to_vault.vault_addr(),
+            recipient: None,
+            min_out: Uint128::one(),
+            leg_min_outs: Some(leg_min_outs),
+        },
+        &[coin(
+            vault_token_balance.u128(),
+            robot.deps.vault_robot.vault_token(),
+        )],
+        unwrap,
+        &admin,
+    );
+    if is_ok {
+        to_vault.assert_vault_token_balance_gt(admin.address(), 0u128);
+    } else {
+        robot.assert_vault_token_balance_eq(admin.address(), vault_token_balance);
+    }
+}
@@ -0,0 +1,178 @@
+use apollo_cw_asset::Asset;
+use common::setup_with_second_vault;
+use cosmwasm_std::{coin, Uint128};
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::ExecuteMsg;
+
+pub mod common;
+
+#[test]
+fn migrate_between_vaults_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, to_vault, admin) = setup_with_second_vault(&runner, 0);
+
+    // Deposit into the AXL/NTRN vault
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(
+                robot.deps.pool_assets[0].clone(),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    let funds = [coin(
+        vault_token_balance.u128(),
+        robot.deps.vault_robot.vault_token(),
+    )];
+    let migrate_msg = |min_out: Uint128| ExecuteMsg::MigrateBetweenVaults {
+        from_vault: robot.vault_addr(),
+        to_vault: to_vault.vault_addr(),
+        recipient: None,
+        min_out,
+        leg_min_outs: None,
+    };
+
+    // Migrate to the ASTRO/NTRN vault, enforcing the minimum amount of its vault
+    // token received
+    robot
+        .zapper_execute(
+            &migrate_msg(Uint128::MAX),
+            &funds,
+            Unwrap::Err("Minimum amount not met"),
+            &admin,
+        )
+        .zapper_execute(&migrate_msg(Uint128::one()), &funds, Unwrap::Ok, &admin)
+        .assert_vault_token_balance_eq(admin.address(), 0u128);
+    to_vault.assert_vault_token_balance_gt(admin.address(), 0u128);
+
+    // The zapper should not hold on to any vault tokens
+    robot.assert_native_token_balance_eq(
+        &robot.vault_zapper_addr,
+        robot.deps.vault_robot.vault_token(),
+        0u128,
+    );
+    robot.assert_native_token_balance_eq(&robot.vault_zapper_addr, to_vault.vault_token(), 0u128);
+}
+
+#[test]
+fn migrate_between_vaults_with_invalid_vaults_fails() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, to_vault, admin) = setup_with_second_vault(&runner, 0);
+
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(
+                robot.deps.vault_pool.lp_token(),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    let funds = [coin(
+        vault_token_balance.u128(),
+        robot.deps.vault_robot.vault_token(),
+    )];
+
+    // Migrating to the same vault is not allowed
+    robot.zapper_execute(
+        &ExecuteMsg::MigrateBetweenVaults {
+            from_vault: robot.vault_addr(),
+            to_vault: robot.vault_addr(),
+            recipient: None,
+            min_out: Uint128::one(),
+            leg_min_outs: None,
+        },
+        &funds,
+        Unwrap::Err("Can not migrate to the same vault"),
+        &admin,
+    );
+
+    // The sent vault tokens must be the vault tokens of the vault migrated from
+    robot.zapper_execute(
+        &ExecuteMsg::MigrateBetweenVaults {
+            from_vault: to_vault.vault_addr(),
+            to_vault: robot.vault_addr(),
+            recipient: None,
+            min_out: Uint128::one(),
+            leg_min_outs: None,
+        },
+        &funds,
+        Unwrap::Err("Invalid vault token sent"),
+        &admin,
+    );
+}
+
+#[test]
+fn withdraw_unlocked_to_vault_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let lock_duration = 300;
+    let (robot, to_vault, admin) = setup_with_second_vault(&runner, lock_duration);
+    let other_user = common::VaultZapperRobot::default_account(&runner);
+
+    robot
+        .zapper_deposit(
+            vec![Asset::new(
+                robot.deps.vault_pool.lp_token(),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_unlock_all(&admin);
+
+    let withdraw_msg = |min_out: Uint128| ExecuteMsg::WithdrawUnlockedToVault {
+        vault_address: robot.vault_addr(),
+        lockup_id: 0,
+        to_vault: to_vault.vault_addr(),
+        recipient: None,
+        min_out,
+        leg_min_outs: None,
+    };
+
+    robot
+        .zapper_execute(
+            &withdraw_msg(Uint128::one()),
+            &[],
+            Unwrap::Err("Claim has not yet matured"),
+            &admin,
+        )
+        .increase_time(lock_duration)
+        // Only the owner of the unlocking position can withdraw it
+        .zapper_execute(
+            &withdraw_msg(Uint128::one()),
+            &[],
+            Unwrap::Err("Unauthorized"),
+            &other_user,
+        )
+        .zapper_execute(
+            &withdraw_msg(Uint128::MAX),
+            &[],
+            Unwrap::Err("Minimum amount not met"),
+            &admin,
+        )
+        .zapper_execute(&withdraw_msg(Uint128::one()), &[], Unwrap::Ok, &admin);
+
+    to_vault.assert_vault_token_balance_gt(admin.address(), 0u128);
+    assert!(robot
+        .zapper_query_user_unlocking_positions_for_vault(&admin.address(), None, None)
+        .is_empty());
+}
@@ -0,0 +1,299 @@
+use std::str::FromStr;
+
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::{
+    astroport_vault_instantiate_msg, VaultRobot, VaultZapperDependencies, VaultZapperRobot,
+    DENOM_CREATION_FEE, DEPENDENCY_ARTIFACTS_DIR, UNOPTIMIZED_PATH,
+};
+use cosmwasm_std::{assert_approx_eq, coin, Addr, Coin, Decimal, Uint128};
+use cw_dex::traits::Pool as PoolTrait;
+use cw_dex_astroport::astroport::factory::PairType;
+use cw_dex_astroport::AstroportPool;
+use cw_it::astroport::robot::AstroportTestRobot;
+use cw_it::astroport::utils::create_astroport_pair;
+use cw_it::helpers::Unwrap;
+use cw_it::test_tube::{Account, SigningAccount};
+use cw_it::{OwnedTestRunner, TestRunner};
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use locked_astroport_vault_test_helpers::robot::LockedAstroportVaultRobot;
+use test_case::test_case;
+use vault_zapper::msg::{ExecuteMsg, Pool, ReceiveChoice};
+
+pub mod common;
+
+/// Sets up the vault zapper with a vault whose base token is the LP token of
+/// an LP(AXL/NTRN)/NTRN pool. Returns the robot, the inner AXL/NTRN pool, a
+/// vault of the ASTRO/NTRN pool to migrate to and the admin.
+fn setup_nested_lp_vault<'a>(
+    runner: &'a TestRunner<'a>,
+) -> (
+    VaultZapperRobot<'a>,
+    AstroportPool,
+    LockedAstroportVaultRobot<'a>,
+    SigningAccount,
+) {
+    let admin = VaultZapperRobot::default_account(runner);
+    let vault_dependencies =
+        LockedAstroportVaultRobot::instantiate_deps(runner, &admin, DEPENDENCY_ARTIFACTS_DIR);
+
+    // Creates the AXL/NTRN pool and sets up the router
+    let (_axl_ntrn_vault, axl_ntrn_pool, astro_ntrn_pool) =
+        LockedAstroportVaultRobot::new_axlr_ntrn_vault(
+            runner,
+            LockedAstroportVaultRobot::contract(runner, DEPENDENCY_ARTIFACTS_DIR),
+            Coin::from_str(DENOM_CREATION_FEE).unwrap(),
+            None,
+            None,
+            None,
+            0,
+            &vault_dependencies,
+            &admin,
+        );
+
+    // Create a pool of the AXL/NTRN LP token and NTRN
+    let ntrn = AssetInfo::native("untrn");
+    let (nested_pair, nested_lp) = create_astroport_pair(
+        runner,
+        &vault_dependencies.astroport_contracts.factory.address,
+        PairType::Xyk {},
+        [axl_ntrn_pool.lp_token().into(), ntrn.clone().into()],
+        None,
+        &admin,
+        Some([
+            Uint128::new(1_000_000_000_000),
+            Uint128::new(1_000_000_000_000),
+        ]),
+    );
+    let nested_pool = AstroportPool {
+        lp_token_addr: Addr::unchecked(&nested_lp),
+        pair_addr: Addr::unchecked(&nested_pair),
+        pair_type: PairType::Xyk {},
+        pool_assets: vec![axl_ntrn_pool.lp_token(), ntrn.clone()],
+        liquidity_manager: axl_ntrn_pool.liquidity_manager.clone(),
+    };
+
+    let vault_init_msg = |pool_addr: String| {
+        astroport_vault_instantiate_msg(&vault_dependencies, &admin, pool_addr, 0, ntrn.clone())
+    };
+    let nested_vault = LockedAstroportVaultRobot::new_with_instantiate_msg(
+        runner,
+        LockedAstroportVaultRobot::contract(runner, DEPENDENCY_ARTIFACTS_DIR),
+        Coin::from_str(DENOM_CREATION_FEE).unwrap(),
+        &vault_init_msg(nested_pair),
+        &vault_dependencies,
+        &admin,
+    );
+    let astro_ntrn_vault = LockedAstroportVaultRobot::new_with_instantiate_msg(
+        runner,
+        LockedAstroportVaultRobot::contract(runner, DEPENDENCY_ARTIFACTS_DIR),
+        Coin::from_str(DENOM_CREATION_FEE).unwrap(),
+        &vault_init_msg(astro_ntrn_pool.pair_addr.to_string()),
+        &vault_dependencies,
+        &admin,
+    );
+
+    let deps = VaultZapperDependencies {
+        astroport_contracts: vault_dependencies.astroport_contracts,
+        cw_dex_router_robot: vault_dependencies.cw_dex_router_robot,
+        liquidity_helper_addr: vault_dependencies.liquidity_helper_addr,
+        vault_robot: VaultRobot::Astroport(nested_vault),
+        pool_assets: nested_pool.pool_assets.clone(),
+        vault_pool: Pool::Astroport(nested_pool),
+    };
+    let robot = VaultZapperRobot::instantiate(runner, deps, UNOPTIMIZED_PATH, &admin);
+
+    (robot, axl_ntrn_pool, astro_ntrn_vault, admin)
+}
+
+#[test_case("uaxl"; "leaf asset of inner pool")]
+#[test_case("untrn"; "leaf asset of both pools")]
+#[test_case("uastro"; "asset not in any pool")]
+fn deposit_into_nested_lp_vault(denom: &str) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, _inner_pool, _astro_ntrn_vault, admin) = setup_nested_lp_vault(&runner);
+
+    let deposit_assets: AssetList =
+        vec![Asset::new(AssetInfo::native(denom), Uint128::new(1000000))].into();
+    let simulated = robot.zapper_query_simulate_deposit(deposit_assets.clone());
+    assert!(!simulated.vault_token_amount.is_zero());
+
+    let vault_token_balance = robot
+        .zapper_deposit(deposit_assets, None, Uint128::one(), Unwrap::Ok, &admin)
+        .query_vault_token_balance(admin.address());
+    assert_approx_eq!(vault_token_balance, simulated.vault_token_amount, "0.01");
+
+    // No LP tokens should be left in the zapper
+    for lp_token in [
+        robot.deps.vault_pool.lp_token(),
+        robot.deps.pool_assets[0].clone(),
+    ] {
+        robot.assert_asset_balance_eq(&lp_token.into(), &robot.vault_zapper_addr, 0u128);
+    }
+}
+
+#[test]
+fn deposit_inner_lp_token_into_nested_lp_vault() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, inner_pool, _astro_ntrn_vault, admin) = setup_nested_lp_vault(&runner);
+
+    // Only the LP token of the inner pool, an asset of the outer pool, is
+    // deposited, so there is no liquidity to provide to the inner pool
+    let inner_lp_token = inner_pool.lp_token();
+    let inner_lp_balance_before =
+        robot.query_asset_balance(&inner_lp_token.clone().into(), &admin.address());
+    robot.provide_liquidity(
+        inner_pool.pair_addr.as_str(),
+        vec![
+            Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000)).into(),
+            Asset::new(AssetInfo::native("untrn"), Uint128::new(1000000)).into(),
+        ],
+        &admin,
+    );
+    let inner_lp_amount = robot
+        .query_asset_balance(&inner_lp_token.clone().into(), &admin.address())
+        - inner_lp_balance_before;
+
+    let deposit_assets: AssetList = vec![Asset::new(inner_lp_token, inner_lp_amount)].into();
+    let simulated = robot.zapper_query_simulate_deposit(deposit_assets.clone());
+    let vault_token_balance = robot
+        .zapper_deposit(deposit_assets, None, Uint128::one(), Unwrap::Ok, &admin)
+        .query_vault_token_balance(admin.address());
+    assert!(!vault_token_balance.is_zero());
+    assert_approx_eq!(vault_token_balance, simulated.vault_token_amount, "0.01");
+}
+
+#[test]
+fn redeem_underlying_from_nested_lp_vault() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, inner_pool, _astro_ntrn_vault, admin) = setup_nested_lp_vault(&runner);
+
+    robot.zapper_deposit(
+        vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    // The underlying assets are the leaf assets of both pools
+    let vault_token_balance = robot.query_vault_token_balance(admin.address());
+    let simulated =
+        robot.zapper_query_simulate_redeem(vault_token_balance, ReceiveChoice::Underlying);
+    let inner_lp_token = inner_pool.lp_token();
+    assert!(simulated.find(&inner_lp_token).is_none());
+
+    let leaf_assets = [AssetInfo::native("uaxl"), AssetInfo::native("untrn")];
+    let balances_before: Vec<Uint128> = leaf_assets
+        .iter()
+        .map(|info| robot.query_asset_balance(&info.clone().into(), &admin.address()))
+        .collect();
+    let inner_lp_balance_before =
+        robot.query_asset_balance(&inner_lp_token.clone().into(), &admin.address());
+
+    // The minimum amounts of the leaf assets are enforced
+    let min_out: AssetList = leaf_assets
+        .iter()
+        .map(|info| simulated.find(info).unwrap().clone())
+        .collect::<Vec<_>>()
+        .into();
+    robot
+        .zapper_redeem_all(None, ReceiveChoice::Underlying, min_out, Unwrap::Ok, &admin)
+        .assert_vault_token_balance_eq(admin.address(), 0u128)
+        .assert_asset_balance_eq(
+            &inner_lp_token.clone().into(),
+            &admin.address(),
+            inner_lp_balance_before,
+        )
+        .assert_asset_balance_eq(&inner_lp_token.into(), &robot.vault_zapper_addr, 0u128);
+
+    for (info, balance_before) in leaf_assets.iter().zip(balances_before) {
+        let received =
+            robot.query_asset_balance(&info.clone().into(), &admin.address()) - balance_before;
+        assert!(!received.is_zero());
+        assert_approx_eq!(received, simulated.find(info).unwrap().amount, "0.01");
+    }
+}
+
+#[test_case(ReceiveChoice::SwapTo(AssetInfo::native("uaxl")); "swap to leaf asset")]
+#[test_case(ReceiveChoice::Basket(vec![
+    (AssetInfo::native("uaxl"), Decimal::percent(30)),
+    (AssetInfo::native("untrn"), Decimal::percent(70)),
+]); "basket of leaf assets")]
+fn redeem_from_nested_lp_vault(receive_choice: ReceiveChoice) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, inner_pool, _astro_ntrn_vault, admin) = setup_nested_lp_vault(&runner);
+
+    robot.zapper_deposit(
+        vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    let vault_token_balance = robot.query_vault_token_balance(admin.address());
+    let simulated = robot.zapper_query_simulate_redeem(vault_token_balance, receive_choice.clone());
+    let balances_before: Vec<Uint128> = simulated
+        .iter()
+        .map(|a| robot.query_asset_balance(&a.info.clone().into(), &admin.address()))
+        .collect();
+
+    robot
+        .zapper_redeem_all(None, receive_choice, AssetList::new(), Unwrap::Ok, &admin)
+        .assert_asset_balance_eq(
+            &inner_pool.lp_token().into(),
+            &robot.vault_zapper_addr,
+            0u128,
+        );
+
+    for (asset, balance_before) in simulated.iter().zip(balances_before) {
+        let received = robot.query_asset_balance(&asset.info.clone().into(), &admin.address())
+            - balance_before;
+        assert!(!received.is_zero());
+        assert_approx_eq!(received, asset.amount, "0.01");
+    }
+}
+
+#[test]
+fn migrate_from_nested_lp_vault() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, inner_pool, to_vault, admin) = setup_nested_lp_vault(&runner);
+
+    robot.zapper_deposit(
+        vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+    let vault_token_balance = robot.query_vault_token_balance(admin.address());
+
+    // Liquidity is withdrawn from both pools, so that only leaf assets are
+    // deposited into the other vault
+    robot
+        .zapper_execute(
+            &ExecuteMsg::MigrateBetweenVaults {
+                from_vault: robot.vault_addr(),
+                to_vault: to_vault.vault_addr(),
+                recipient: None,
+                min_out: Uint128::one(),
+                leg_min_outs: None,
+            },
+            &[coin(
+                vault_token_balance.u128(),
+                robot.deps.vault_robot.vault_token(),
+            )],
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_eq(admin.address(), 0u128);
+    to_vault.assert_vault_token_balance_gt(admin.address(), 0u128);
+    for lp_token in [robot.deps.vault_pool.lp_token(), inner_pool.lp_token()] {
+        robot.assert_asset_balance_eq(&lp_token.into(), &robot.vault_zapper_addr, 0u128);
+    }
+}
@@ -0,0 +1,157 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetInfoUnchecked, AssetList};
+use common::mock_oracle::instantiate_mock_oracle;
+use common::setup;
+use cosmwasm_std::{coin, Decimal, Uint128};
+use cw_it::helpers::Unwrap;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use test_case::test_case;
+use vault_zapper::msg::{ConfigUpdates, ExecuteMsg, ReceiveChoice};
+use vault_zapper::oracle::OracleConfig;
+
+pub mod common;
+
+#[test]
+fn update_oracle_config_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let user = common::VaultZapperRobot::default_account(&runner);
+    let oracle = instantiate_mock_oracle(&runner, vec![], &admin);
+
+    assert_eq!(robot.zapper_query_oracle_config(), None);
+
+    let updates = |max_deviation: Decimal| ConfigUpdates {
+        oracle_config: Some(OracleConfig {
+            max_deviation,
+            oracles: vec![(AssetInfoUnchecked::native("uaxl"), oracle.clone())],
+        }),
+        ..Default::default()
+    };
+    robot
+        .zapper_update_config(
+            updates(Decimal::percent(5)),
+            Unwrap::Err("Caller is not the contract's current owner"),
+            &user,
+        )
+        .zapper_update_config(
+            updates(Decimal::one()),
+            Unwrap::Err("Max deviation must be less than 100%"),
+            &admin,
+        )
+        .zapper_update_config(updates(Decimal::percent(5)), Unwrap::Ok, &admin);
+
+    let config = robot.zapper_query_oracle_config().unwrap();
+    assert_eq!(config.max_deviation, Decimal::percent(5));
+    assert_eq!(config.oracles.len(), 1);
+    assert_eq!(config.oracles[0].0, AssetInfo::native("uaxl"));
+    assert_eq!(config.oracles[0].1.to_string(), oracle);
+}
+
+#[test_case(Decimal::one(), Unwrap::Ok ; "rate matches oracle")]
+#[test_case(Decimal::percent(200), Unwrap::Err("Did not receive minimum amount") ; "rate deviates from oracle")]
+fn redeem_swap_is_checked_against_oracle(first_asset_price: Decimal, unwrap: Unwrap) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    robot.zapper_deposit(
+        vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            Uint128::new(1000000),
+        )]
+        .into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+    let vault_token_balance = robot.query_vault_token_balance(admin.address());
+
+    // The pool holds both assets at a 1:1 ratio, so the withdrawn first asset
+    // is swapped at a rate of about 1 into the second
+    let pool_assets = robot.deps.pool_assets.clone();
+    let oracle = instantiate_mock_oracle(
+        &runner,
+        vec![
+            (pool_assets[0].clone(), first_asset_price),
+            (pool_assets[1].clone(), Decimal::one()),
+        ],
+        &admin,
+    );
+    robot.zapper_update_config(
+        ConfigUpdates {
+            oracle_config: Some(OracleConfig {
+                max_deviation: Decimal::percent(5),
+                oracles: pool_assets
+                    .iter()
+                    .map(|info| (info.clone().into(), oracle.clone()))
+                    .collect(),
+            }),
+            ..Default::default()
+        },
+        Unwrap::Ok,
+        &admin,
+    );
+
+    robot.zapper_execute(
+        &ExecuteMsg::Redeem {
+            vault_address: robot.vault_addr(),
+            recipient: None,
+            receive_choice: ReceiveChoice::SwapTo(pool_assets[1].clone()),
+            min_out: AssetList::new().into(),
+            referral: None,
+            leg_min_outs: None,
+        },
+        &[coin(
+            vault_token_balance.u128(),
+            robot.deps.vault_robot.vault_token(),
+        )],
+        unwrap,
+        &admin,
+    );
+}
+
+#[test_case(Decimal::from_ratio(2u128, 1_000_000u128), Unwrap::Ok ; "value matches oracle")]
+#[test_case(Decimal::from_ratio(1u128, 1_000_000u128), Unwrap::Err("Value received deviates too far from the oracle price") ; "value deviates from oracle")]
+fn deposit_is_checked_against_oracle(vault_token_price: Decimal, unwrap: Unwrap) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    // An LP token is worth about 2 of either pool asset and is worth 10^6 vault
+    // tokens
+    let deposit_asset = robot.deps.pool_assets[0].clone();
+    let vault_token = AssetInfo::native(robot.deps.vault_robot.vault_token());
+    let oracle = instantiate_mock_oracle(
+        &runner,
+        vec![
+            (deposit_asset.clone(), Decimal::one()),
+            (vault_token.clone(), vault_token_price),
+        ],
+        &admin,
+    );
+    robot.zapper_update_config(
+        ConfigUpdates {
+            oracle_config: Some(OracleConfig {
+                max_deviation: Decimal::percent(5),
+                oracles: vec![
+                    (deposit_asset.clone().into(), oracle.clone()),
+                    (vault_token.into(), oracle),
+                ],
+            }),
+            ..Default::default()
+        },
+        Unwrap::Ok,
+        &admin,
+    );
+
+    robot.zapper_deposit(
+        vec![Asset::new(deposit_asset, Uint128::new(1000000))].into(),
+        None,
+        Uint128::one(),
+        unwrap,
+        &admin,
+    );
+}
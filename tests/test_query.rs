@@ -1,12 +1,13 @@
 use std::str::FromStr;
 
-use apollo_cw_asset::{Asset, AssetInfo};
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
 use common::{
-    setup, VaultRobot, VaultZapperDependencies, VaultZapperRobot, DENOM_CREATION_FEE,
-    DEPENDENCY_ARTIFACTS_DIR, UNOPTIMIZED_PATH,
+    setup, setup_with_second_vault, VaultRobot, VaultZapperDependencies, VaultZapperRobot,
+    DENOM_CREATION_FEE, DEPENDENCY_ARTIFACTS_DIR, UNOPTIMIZED_PATH,
 };
-use cosmwasm_std::{coin, Addr, Coin, Decimal, Timestamp, Uint128};
+use cosmwasm_std::{assert_approx_eq, coin, Addr, Coin, Decimal, Timestamp, Uint128};
 use cw_dex::traits::Pool as PoolTrait;
+use cw_it::astroport::robot::AstroportTestRobot;
 use cw_it::helpers::Unwrap;
 use cw_it::robot::TestRobot;
 use cw_it::test_tube::Account;
@@ -19,7 +20,9 @@ use locked_astroport_vault::msg::InstantiateMsg as AstroportVaultInstantiateMsg;
 use locked_astroport_vault::state::FeeConfig;
 use locked_astroport_vault_test_helpers::robot::LockedAstroportVaultRobot;
 use locked_astroport_vault_test_helpers::router::CwDexRouterRobot;
+use test_case::test_case;
 use vault_zapper::msg::{Pool, ReceiveChoice};
+use vault_zapper::state::MAX_SCANNED_UNLOCKING_POSITIONS;
 
 pub mod common;
 
@@ -104,7 +107,10 @@ fn query_unlocking_positions_for_one_vault() {
         )),
     };
     robot
-        .assert_zapper_has_unlocking_positions(&admin.address(), &[unlocking_position_0.clone()])
+        .assert_zapper_has_unlocking_positions(
+            &admin.address(),
+            std::slice::from_ref(&unlocking_position_0),
+        )
         .zapper_unlock(vault_token_balance.u128() / 2, &admin);
 
     // Query the unlocking positions
@@ -457,3 +463,332 @@ fn query_unlocking_positions_for_two_vaults() {
         &vec![second_vault_first_pos.clone()]
     );
 }
+
+#[test]
+fn query_annotated_unlocking_positions() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 300);
+
+    // Unlock half of the deposited amount now and the other half 100 seconds
+    // later
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    robot
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin)
+        .increase_time(100)
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin);
+
+    let positions = robot
+        .zapper_query_annotated_user_unlocking_positions(&admin.address(), None, None, None, None)
+        .positions;
+    assert_eq!(positions.len(), 2);
+    for (position, seconds_remaining) in positions.iter().zip([200, 300]) {
+        assert_eq!(position.vault_address, Addr::unchecked(robot.vault_addr()));
+        assert!(!position.is_claimable);
+        assert_eq!(position.seconds_remaining, Some(seconds_remaining));
+        assert_eq!(position.blocks_remaining, None);
+
+        // The value is estimated in the underlying assets of the LP token
+        assert_eq!(position.estimated_underlying_assets.len(), 2);
+    }
+    assert!(robot
+        .zapper_query_annotated_user_unlocking_positions(
+            &admin.address(),
+            None,
+            None,
+            None,
+            Some(true)
+        )
+        .positions
+        .is_empty());
+
+    // Only the first position is claimable after 200 more seconds
+    robot.increase_time(200);
+    let positions = robot
+        .zapper_query_annotated_user_unlocking_positions(&admin.address(), None, None, None, None)
+        .positions;
+    assert!(positions[0].is_claimable);
+    assert_eq!(positions[0].seconds_remaining, Some(0));
+    assert!(!positions[1].is_claimable);
+    assert_eq!(positions[1].seconds_remaining, Some(100));
+    let claimable = robot
+        .zapper_query_annotated_user_unlocking_positions(
+            &admin.address(),
+            None,
+            None,
+            None,
+            Some(true),
+        )
+        .positions;
+    assert_eq!(claimable, vec![positions[0].clone()]);
+
+    // The estimated value is received when withdrawing
+    let balances_before: Vec<Uint128> = positions[0]
+        .estimated_underlying_assets
+        .iter()
+        .map(|a| robot.query_asset_balance(&a.info.clone().into(), &admin.address()))
+        .collect();
+    robot.zapper_withdraw_unlocked(
+        0,
+        None,
+        ReceiveChoice::Underlying,
+        vec![],
+        Unwrap::Ok,
+        &admin,
+    );
+    for (asset, balance_before) in positions[0]
+        .estimated_underlying_assets
+        .iter()
+        .zip(balances_before)
+    {
+        let received = robot.query_asset_balance(&asset.info.clone().into(), &admin.address())
+            - balance_before;
+        assert_approx_eq!(received, asset.amount, "0.01");
+    }
+}
+
+#[test_case(None; "base token")]
+#[test_case(Some(0); "one asset of pool")]
+#[test_case(Some(1); "other asset of pool")]
+fn query_simulate_deposit(pool_asset_idx: Option<usize>) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    let deposit_amount = Uint128::new(1000000);
+    let deposit_asset_info = match pool_asset_idx {
+        Some(idx) => robot.deps.pool_assets[idx].clone(),
+        None => robot.deps.vault_pool.lp_token(),
+    };
+    let assets = vec![Asset::new(deposit_asset_info, deposit_amount)];
+
+    let simulated = robot.zapper_query_simulate_deposit(assets.clone().into());
+    assert!(!simulated.vault_token_amount.is_zero());
+
+    let vault_token_balance = robot
+        .zapper_deposit(
+            assets.into(),
+            None,
+            simulated.vault_token_amount * Decimal::percent(99),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+
+    assert_approx_eq!(vault_token_balance, simulated.vault_token_amount, "0.01");
+}
+
+#[test]
+fn query_simulate_deposit_asset_not_in_pool() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    let assets = vec![Asset::new(
+        AssetInfo::native("uastro"),
+        Uint128::new(1000000),
+    )];
+
+    let simulated = robot.zapper_query_simulate_deposit(assets.clone().into());
+    assert!(!simulated.vault_token_amount.is_zero());
+    assert_eq!(simulated.liquidated_assets.len(), 1);
+
+    let vault_token_balance = robot
+        .zapper_deposit(assets.into(), None, Uint128::one(), Unwrap::Ok, &admin)
+        .query_vault_token_balance(admin.address());
+
+    assert_approx_eq!(vault_token_balance, simulated.vault_token_amount, "0.01");
+}
+
+#[test_case(ReceiveChoice::BaseToken; "base token")]
+#[test_case(ReceiveChoice::Underlying; "underlying")]
+#[test_case(ReceiveChoice::SwapTo(AssetInfo::native("uastro")); "swap to asset not in pool")]
+#[test_case(ReceiveChoice::SwapTo(AssetInfo::native("uaxl")); "swap to asset in pool")]
+#[test_case(ReceiveChoice::Basket(vec![
+    (AssetInfo::native("uaxl"), Decimal::percent(70)),
+    (AssetInfo::native("uastro"), Decimal::percent(30)),
+]); "basket")]
+fn query_simulate_redeem(receive_choice: ReceiveChoice) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    // Deposit the LP token of the vault
+    let deposit_asset = Asset::new(robot.deps.vault_pool.lp_token(), Uint128::new(1000000));
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![deposit_asset].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+
+    let simulated = robot.zapper_query_simulate_redeem(vault_token_balance, receive_choice.clone());
+    assert!(simulated.len() > 0);
+    let balances_before: Vec<Uint128> = simulated
+        .iter()
+        .map(|a| robot.query_asset_balance(&a.info.clone().into(), &admin.address()))
+        .collect();
+
+    robot.zapper_redeem_all(
+        None,
+        receive_choice,
+        AssetList::from(
+            simulated
+                .iter()
+                .map(|a| Asset::new(a.info.clone(), a.amount * Decimal::percent(99)))
+                .collect::<Vec<_>>(),
+        ),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    for (asset, balance_before) in simulated.iter().zip(balances_before) {
+        let received = robot.query_asset_balance(&asset.info.clone().into(), &admin.address())
+            - balance_before;
+        assert_approx_eq!(received, asset.amount, "0.01");
+    }
+}
+
+#[test]
+fn query_claimable_annotated_unlocking_positions_fills_limit() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, 300);
+
+    robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_deposit_to_vault(
+            vec![Asset::new(
+                AssetInfo::native("uastro"),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            &second_vault.vault_addr(),
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        );
+
+    // Unlock in the vault that comes last in the pagination first, so that the
+    // first position in the pagination is not yet claimable when the other one
+    // is
+    let mut vaults = [
+        (robot.vault_addr(), robot.deps.vault_robot.vault_token()),
+        (second_vault.vault_addr(), second_vault.vault_token()),
+    ];
+    vaults.sort();
+    for (vault_addr, vault_token) in vaults.iter().rev() {
+        let balance = robot.query_native_token_balance(admin.address(), vault_token);
+        robot
+            .zapper_unlock_from_vault(vault_addr, &[coin(balance.u128(), vault_token)], &admin)
+            .increase_time(100);
+    }
+    robot.increase_time(100);
+
+    let res = robot.zapper_query_annotated_user_unlocking_positions(
+        &admin.address(),
+        None,
+        None,
+        Some(1),
+        Some(true),
+    );
+    assert_eq!(res.positions.len(), 1);
+    let claimable = &res.positions[0];
+    assert_eq!(claimable.vault_address, Addr::unchecked(&vaults[1].0));
+    assert!(claimable.is_claimable);
+
+    // The query continues after the returned position and reports that all
+    // positions have been scanned
+    let (start_after_vault_addr, start_after_id) = res.start_after.unwrap();
+    assert_eq!(
+        (start_after_vault_addr.as_str(), start_after_id),
+        (vaults[1].0.as_str(), claimable.position.id)
+    );
+    let res = robot.zapper_query_annotated_user_unlocking_positions(
+        &admin.address(),
+        Some(start_after_vault_addr.to_string()),
+        Some(start_after_id),
+        Some(1),
+        Some(true),
+    );
+    assert!(res.positions.is_empty());
+    assert_eq!(res.start_after, None);
+}
+
+#[test]
+fn query_annotated_unlocking_positions_scans_at_most_max_positions() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 300);
+
+    let num_positions = MAX_SCANNED_UNLOCKING_POSITIONS + 1;
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    for _ in 0..num_positions {
+        robot.zapper_unlock(vault_token_balance.u128() / num_positions as u128, &admin);
+    }
+
+    // None of the positions are claimable, so the query stops after scanning
+    // the maximum number of positions and continues from there
+    let res = robot.zapper_query_annotated_user_unlocking_positions(
+        &admin.address(),
+        None,
+        None,
+        None,
+        Some(true),
+    );
+    assert!(res.positions.is_empty());
+    let (start_after_vault_addr, start_after_id) = res.start_after.unwrap();
+    let res = robot.zapper_query_annotated_user_unlocking_positions(
+        &admin.address(),
+        Some(start_after_vault_addr.to_string()),
+        Some(start_after_id),
+        None,
+        Some(true),
+    );
+    assert!(res.positions.is_empty());
+    assert_eq!(res.start_after, None);
+
+    // Without filtering, at most the maximum number of positions are returned
+    let res = robot.zapper_query_annotated_user_unlocking_positions(
+        &admin.address(),
+        None,
+        None,
+        Some(num_positions),
+        None,
+    );
+    assert_eq!(
+        res.positions.len(),
+        MAX_SCANNED_UNLOCKING_POSITIONS as usize
+    );
+    let last = res.positions.last().unwrap();
+    assert_eq!(
+        res.start_after,
+        Some((last.vault_address.clone(), last.position.id))
+    );
+}
@@ -0,0 +1,90 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::wrapper_vault::instantiate_reentrant_wrapper_vault;
+use cosmwasm_std::{coin, to_json_binary, Empty, Uint128, WasmMsg};
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::{Account, Module, Wasm};
+use cw_it::OwnedTestRunner;
+use cw_vault_standard::VaultStandardExecuteMsg;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::{ExecuteMsg, ReceiveChoice};
+
+pub mod common;
+
+#[test]
+fn vault_calling_back_into_zapper_during_redeem_fails() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = common::setup(&runner, 0);
+    let inner_vault_token = robot.deps.vault_robot.vault_token();
+    let stranded = Uint128::new(1000);
+
+    // The zapper holds some stranded inner vault tokens
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    robot.send_native_tokens(
+        &admin,
+        &robot.vault_zapper_addr,
+        stranded,
+        &inner_vault_token,
+    );
+
+    // A vault over the inner vault token that zaps some of its base tokens via
+    // the zapper while the zapper redeems from it. The reservation of the
+    // redemption must not be released by the nested zap.
+    let zap_amount = Uint128::one();
+    let on_redeem = vec![WasmMsg::Execute {
+        contract_addr: robot.vault_zapper_addr.clone(),
+        msg: to_json_binary(&ExecuteMsg::ZapBaseTokens {
+            base_token: Asset::native(&inner_vault_token, zap_amount).into(),
+            recipient: None,
+            receive_choice: ReceiveChoice::BaseToken,
+            min_out: AssetList::new().into(),
+            referral: None,
+            leg_min_outs: None,
+        })
+        .unwrap(),
+        funds: vec![coin(zap_amount.u128(), &inner_vault_token)],
+    }
+    .into()];
+    let (wrapper_vault, wrapper_vault_token) =
+        instantiate_reentrant_wrapper_vault(&runner, &inner_vault_token, on_redeem, &admin);
+
+    // Give the vault some base tokens of its own to zap
+    robot.send_native_tokens(&admin, &wrapper_vault, zap_amount, &inner_vault_token);
+    let deposit_amount = vault_token_balance - stranded - zap_amount;
+    Wasm::new(&runner)
+        .execute(
+            &wrapper_vault,
+            &VaultStandardExecuteMsg::<Empty>::Deposit {
+                amount: deposit_amount,
+                recipient: None,
+            },
+            &[coin(deposit_amount.u128(), &inner_vault_token)],
+            &admin,
+        )
+        .unwrap();
+
+    robot
+        .zapper_execute(
+            &ExecuteMsg::Redeem {
+                vault_address: wrapper_vault,
+                recipient: None,
+                receive_choice: ReceiveChoice::BaseToken,
+                min_out: AssetList::new().into(),
+                referral: None,
+                leg_min_outs: None,
+            },
+            &[coin(deposit_amount.u128(), &wrapper_vault_token)],
+            Unwrap::Err("Another operation is in progress"),
+            &admin,
+        )
+        .assert_native_token_balance_eq(&robot.vault_zapper_addr, &inner_vault_token, stranded);
+}
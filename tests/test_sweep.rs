@@ -0,0 +1,122 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::setup;
+use common::wrapper_vault::instantiate_reentrant_wrapper_vault;
+use cosmwasm_std::{coin, to_json_binary, CosmosMsg, Empty, Uint128, WasmMsg};
+use cw_it::astroport::robot::AstroportTestRobot;
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::{Account, Module, Wasm};
+use cw_it::OwnedTestRunner;
+use cw_vault_standard::VaultStandardExecuteMsg;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::{ExecuteMsg, ReceiveChoice};
+
+pub mod common;
+
+#[test]
+fn sweep_stranded_assets_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let user = common::VaultZapperRobot::default_account(&runner);
+
+    // Tokens sent to the zapper directly are stranded in it
+    let asset = AssetInfo::native("uastro");
+    let stranded = Uint128::new(1000);
+    robot.send_native_tokens(&admin, &robot.vault_zapper_addr, stranded, "uastro");
+    assert_eq!(
+        robot.zapper_query_contract_balances(vec![asset.clone(), AssetInfo::native("uaxl")]),
+        AssetList::from(vec![
+            Asset::new(asset.clone(), stranded),
+            Asset::new(AssetInfo::native("uaxl"), Uint128::zero()),
+        ])
+    );
+
+    // Only the owner can sweep them
+    let user_balance = robot.query_asset_balance(&asset.clone().into(), &user.address());
+    let assets: AssetList = vec![Asset::new(asset.clone(), stranded)].into();
+    robot
+        .zapper_sweep(
+            assets.clone(),
+            Some(user.address()),
+            Unwrap::Err("Caller is not the contract's current owner"),
+            &user,
+        )
+        .zapper_sweep(assets, Some(user.address()), Unwrap::Ok, &admin)
+        .assert_asset_balance_eq(&asset.clone().into(), &robot.vault_zapper_addr, 0u128)
+        .assert_asset_balance_eq(&asset.into(), &user.address(), user_balance + stranded);
+}
+
+#[test]
+fn sweep_is_refused_during_a_callback_chain() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+    let inner_vault_token = robot.deps.vault_robot.vault_token();
+
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+
+    // A vault that becomes the owner of the zapper and sweeps the redeemed
+    // inner vault tokens while the zapper redeems from it
+    let zapper_msg = |msg: &ExecuteMsg| -> CosmosMsg {
+        WasmMsg::Execute {
+            contract_addr: robot.vault_zapper_addr.clone(),
+            msg: to_json_binary(msg).unwrap(),
+            funds: vec![],
+        }
+        .into()
+    };
+    let on_redeem = vec![
+        zapper_msg(&ExecuteMsg::UpdateOwnership(
+            cw_ownable::Action::AcceptOwnership,
+        )),
+        zapper_msg(&ExecuteMsg::Sweep {
+            assets: AssetList::from(vec![Asset::native(&inner_vault_token, vault_token_balance)])
+                .into(),
+            recipient: None,
+        }),
+    ];
+    let (wrapper_vault, wrapper_vault_token) =
+        instantiate_reentrant_wrapper_vault(&runner, &inner_vault_token, on_redeem, &admin);
+    Wasm::new(&runner)
+        .execute(
+            &wrapper_vault,
+            &VaultStandardExecuteMsg::<Empty>::Deposit {
+                amount: vault_token_balance,
+                recipient: None,
+            },
+            &[coin(vault_token_balance.u128(), &inner_vault_token)],
+            &admin,
+        )
+        .unwrap();
+    robot.zapper_update_ownership(
+        cw_ownable::Action::TransferOwnership {
+            new_owner: wrapper_vault.clone(),
+            expiry: None,
+        },
+        Unwrap::Ok,
+        &admin,
+    );
+
+    robot.zapper_execute(
+        &ExecuteMsg::Redeem {
+            vault_address: wrapper_vault,
+            recipient: None,
+            receive_choice: ReceiveChoice::BaseToken,
+            min_out: AssetList::new().into(),
+            referral: None,
+            leg_min_outs: None,
+        },
+        &[coin(vault_token_balance.u128(), &wrapper_vault_token)],
+        Unwrap::Err("Another operation is in progress"),
+        &admin,
+    );
+}
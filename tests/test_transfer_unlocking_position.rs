@@ -0,0 +1,68 @@
+use apollo_cw_asset::{Asset, AssetInfo};
+use common::setup;
+use cosmwasm_std::Uint128;
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::{ExecuteMsg, ReceiveChoice};
+
+pub mod common;
+
+#[test]
+fn transfer_unlocking_position_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let lock_duration = 300;
+    let (robot, admin) = setup(&runner, lock_duration);
+    let new_owner = common::VaultZapperRobot::default_account(&runner);
+
+    robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_unlock_all(&admin);
+    let positions =
+        robot.zapper_query_user_unlocking_positions_for_vault(&admin.address(), None, None);
+    let transfer_msg = ExecuteMsg::TransferUnlockingPosition {
+        vault_address: robot.vault_addr(),
+        lockup_id: 0,
+        new_owner: new_owner.address(),
+    };
+
+    // Only the owner of the position can transfer it
+    robot
+        .zapper_execute(&transfer_msg, &[], Unwrap::Err("Unauthorized"), &new_owner)
+        .zapper_execute(&transfer_msg, &[], Unwrap::Ok, &admin)
+        .assert_zapper_has_unlocking_positions(&admin.address(), &[])
+        .assert_zapper_has_unlocking_positions(&new_owner.address(), &positions)
+        .zapper_execute(&transfer_msg, &[], Unwrap::Err("Unauthorized"), &admin);
+
+    // The new owner can withdraw from the position, and the previous owner can not
+    let balance_before = robot.query_native_token_balance(new_owner.address(), "uaxl");
+    robot
+        .increase_time(lock_duration)
+        .zapper_withdraw_unlocked(
+            0,
+            None,
+            ReceiveChoice::SwapTo(AssetInfo::native("uaxl")),
+            vec![],
+            Unwrap::Err("Unauthorized"),
+            &admin,
+        )
+        .zapper_withdraw_unlocked(
+            0,
+            None,
+            ReceiveChoice::SwapTo(AssetInfo::native("uaxl")),
+            vec![],
+            Unwrap::Ok,
+            &new_owner,
+        )
+        .assert_zapper_has_unlocking_positions(&new_owner.address(), &[]);
+    assert!(robot.query_native_token_balance(new_owner.address(), "uaxl") > balance_before);
+}
@@ -0,0 +1,227 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::wrapper_vault::instantiate_wrapper_vault;
+use cosmwasm_std::{assert_approx_eq, coin, Decimal, Uint128};
+use cw_it::astroport::robot::AstroportTestRobot;
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::{Account, Module, Wasm};
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::{ExecuteMsg, QueryMsg, ReceiveChoice, SimulateDepositResponse};
+
+pub mod common;
+
+#[test]
+fn deposit_into_and_redeem_from_vault_of_vaults() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = common::setup(&runner, 0);
+    let inner_vault_token = robot.deps.vault_robot.vault_token();
+    let (wrapper_vault, wrapper_vault_token) =
+        instantiate_wrapper_vault(&runner, &inner_vault_token, &admin);
+    let wasm = Wasm::new(&runner);
+
+    // Zap AXL into the wrapper vault through the inner AXL/NTRN vault
+    let deposit_assets: AssetList =
+        vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into();
+    let simulated: SimulateDepositResponse = wasm
+        .query(
+            &robot.vault_zapper_addr,
+            &QueryMsg::SimulateDeposit {
+                assets: deposit_assets.clone().into(),
+                vault_address: wrapper_vault.clone(),
+                referral: None,
+            },
+        )
+        .unwrap();
+    robot.zapper_deposit_to_vault(
+        deposit_assets,
+        None,
+        &wrapper_vault,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+    let wrapper_balance = robot.query_native_token_balance(admin.address(), &wrapper_vault_token);
+    assert!(!wrapper_balance.is_zero());
+    assert_approx_eq!(wrapper_balance, simulated.vault_token_amount, "0.01");
+    robot
+        .assert_vault_token_balance_eq(admin.address(), 0u128)
+        .assert_native_token_balance_eq(&robot.vault_zapper_addr, &inner_vault_token, 0u128);
+
+    let redeem_msg = |receive_choice: ReceiveChoice| ExecuteMsg::Redeem {
+        vault_address: wrapper_vault.clone(),
+        recipient: None,
+        receive_choice,
+        min_out: AssetList::new().into(),
+        referral: None,
+        leg_min_outs: None,
+    };
+
+    // Redeeming the base token returns the inner vault's token
+    let half = wrapper_balance / Uint128::new(2);
+    robot
+        .zapper_execute(
+            &redeem_msg(ReceiveChoice::BaseToken),
+            &[coin(half.u128(), &wrapper_vault_token)],
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_eq(admin.address(), half);
+
+    // Redeeming the underlying assets unwraps through the inner vault and its
+    // pool
+    let remaining = wrapper_balance - half;
+    let simulated: AssetList = wasm
+        .query(
+            &robot.vault_zapper_addr,
+            &QueryMsg::SimulateRedeem {
+                vault_address: wrapper_vault.clone(),
+                vault_token_amount: remaining,
+                receive_choice: ReceiveChoice::Underlying,
+                referral: None,
+            },
+        )
+        .unwrap();
+    let underlying = [AssetInfo::native("uaxl"), AssetInfo::native("untrn")];
+    let balances_before: Vec<Uint128> = underlying
+        .iter()
+        .map(|info| robot.query_asset_balance(&info.clone().into(), &admin.address()))
+        .collect();
+    robot.zapper_execute(
+        &redeem_msg(ReceiveChoice::Underlying),
+        &[coin(remaining.u128(), &wrapper_vault_token)],
+        Unwrap::Ok,
+        &admin,
+    );
+    for (info, balance_before) in underlying.iter().zip(balances_before) {
+        let received =
+            robot.query_asset_balance(&info.clone().into(), &admin.address()) - balance_before;
+        assert!(!received.is_zero());
+        assert_approx_eq!(received, simulated.find(info).unwrap().amount, "0.01");
+    }
+    robot
+        .assert_vault_token_balance_eq(admin.address(), half)
+        .assert_native_token_balance_eq(&robot.vault_zapper_addr, &inner_vault_token, 0u128);
+}
+
+#[test]
+fn redeem_basket_containing_inner_vault_token_fails() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = common::setup(&runner, 0);
+    let inner_vault_token = robot.deps.vault_robot.vault_token();
+    let (wrapper_vault, wrapper_vault_token) =
+        instantiate_wrapper_vault(&runner, &inner_vault_token, &admin);
+
+    robot.zapper_deposit_to_vault(
+        vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+        None,
+        &wrapper_vault,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+    let wrapper_balance = robot.query_native_token_balance(admin.address(), &wrapper_vault_token);
+
+    robot.zapper_execute(
+        &ExecuteMsg::Redeem {
+            vault_address: wrapper_vault,
+            recipient: None,
+            receive_choice: ReceiveChoice::Basket(vec![
+                (AssetInfo::native(&inner_vault_token), Decimal::percent(50)),
+                (AssetInfo::native("uaxl"), Decimal::percent(50)),
+            ]),
+            min_out: AssetList::new().into(),
+            referral: None,
+            leg_min_outs: None,
+        },
+        &[coin(wrapper_balance.u128(), wrapper_vault_token)],
+        Unwrap::Err("can not contain the base token when it is a vault token"),
+        &admin,
+    );
+}
+
+#[test]
+fn migrate_from_vault_of_vaults_into_inner_vault() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = common::setup(&runner, 0);
+    let inner_vault_token = robot.deps.vault_robot.vault_token();
+    let (wrapper_vault, wrapper_vault_token) =
+        instantiate_wrapper_vault(&runner, &inner_vault_token, &admin);
+
+    robot.zapper_deposit_to_vault(
+        vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+        None,
+        &wrapper_vault,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+    let wrapper_balance = robot.query_native_token_balance(admin.address(), &wrapper_vault_token);
+
+    // The redeemed inner vault tokens are redeemed from the inner vault as well,
+    // so that its base token can be deposited
+    robot
+        .zapper_execute(
+            &ExecuteMsg::MigrateBetweenVaults {
+                from_vault: wrapper_vault,
+                to_vault: robot.vault_addr(),
+                recipient: None,
+                min_out: Uint128::one(),
+                leg_min_outs: None,
+            },
+            &[coin(wrapper_balance.u128(), &wrapper_vault_token)],
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_native_token_balance_eq(admin.address(), &wrapper_vault_token, 0u128)
+        .assert_vault_token_balance_gt(admin.address(), 0u128)
+        .assert_native_token_balance_eq(&robot.vault_zapper_addr, &inner_vault_token, 0u128)
+        .assert_asset_balance_eq(
+            &robot.deps.vault_pool.lp_token().into(),
+            &robot.vault_zapper_addr,
+            0u128,
+        );
+}
+
+#[test]
+fn migrate_from_inner_vault_into_vault_of_vaults() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = common::setup(&runner, 0);
+    let inner_vault_token = robot.deps.vault_robot.vault_token();
+    let (wrapper_vault, wrapper_vault_token) =
+        instantiate_wrapper_vault(&runner, &inner_vault_token, &admin);
+
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+
+    // The sent vault tokens are the base token of the wrapper vault, so they are
+    // not reserved as balances the zapper already held
+    robot
+        .zapper_execute(
+            &ExecuteMsg::MigrateBetweenVaults {
+                from_vault: robot.vault_addr(),
+                to_vault: wrapper_vault,
+                recipient: None,
+                min_out: Uint128::one(),
+                leg_min_outs: None,
+            },
+            &[coin(vault_token_balance.u128(), &inner_vault_token)],
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_eq(admin.address(), 0u128)
+        .assert_native_token_balance_gt(admin.address(), &wrapper_vault_token, 0u128)
+        .assert_native_token_balance_eq(&robot.vault_zapper_addr, &inner_vault_token, 0u128)
+        .assert_native_token_balance_eq(&robot.vault_zapper_addr, &wrapper_vault_token, 0u128);
+}
@@ -1,13 +1,14 @@
-use apollo_cw_asset::{Asset, AssetInfo, AssetList, AssetUnchecked};
+use apollo_cw_asset::{Asset, AssetInfo, AssetList, AssetListUnchecked, AssetUnchecked};
 use common::setup;
-use cosmwasm_std::{Decimal, Uint128};
+use cosmwasm_std::{coin, Decimal, Uint128};
 use cw_it::astroport::robot::AstroportTestRobot;
 use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
 use cw_it::test_tube::Account;
 use cw_it::OwnedTestRunner;
 use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
 use test_case::test_case;
-use vault_zapper::msg::ReceiveChoice;
+use vault_zapper::msg::{ExecuteMsg, ReceiveChoice};
 
 pub mod common;
 
@@ -431,3 +432,123 @@ fn redeem_both_assets_of_pool(lock_duration: u64) {
         .assert_asset_balance_approx_eq(asset1, &admin.address(), asset1_balance, max_rel_diff)
         .assert_asset_balance_approx_eq(asset2, &admin.address(), asset2_balance, max_rel_diff);
 }
+
+#[test_case(vec![]; "empty")]
+#[test_case(vec![
+    (AssetInfo::native("uaxl"), Decimal::percent(70)),
+    (AssetInfo::native("uastro"), Decimal::percent(20)),
+]; "weights do not sum to one")]
+#[test_case(vec![
+    (AssetInfo::native("uaxl"), Decimal::percent(50)),
+    (AssetInfo::native("uaxl"), Decimal::percent(50)),
+]; "duplicate asset")]
+fn redeem_to_invalid_basket_fails(basket: Vec<(AssetInfo, Decimal)>) {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    let deposit_asset = Asset::new(robot.deps.vault_pool.lp_token(), Uint128::new(1000000));
+    robot
+        .zapper_deposit(
+            vec![deposit_asset].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_redeem_all(
+            None,
+            ReceiveChoice::Basket(basket),
+            AssetList::new(),
+            Unwrap::Err("Invalid basket"),
+            &admin,
+        );
+}
+
+#[test]
+fn redeem_leaves_balances_already_held_by_zapper_untouched() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    robot.zapper_deposit(
+        vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            Uint128::new(1000000),
+        )]
+        .into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    // Balances the zapper already held are not swapped or sent to the recipient
+    // of the redemption
+    let requested_asset = robot.deps.pool_assets[1].clone();
+    let dust = Uint128::new(1000);
+    let denom = match &requested_asset {
+        AssetInfo::Native(denom) => denom.clone(),
+        AssetInfo::Cw20(_) => panic!("Pool asset is not native"),
+    };
+    robot.send_native_tokens(&admin, &robot.vault_zapper_addr, dust, denom);
+
+    robot
+        .zapper_redeem_all(
+            None,
+            ReceiveChoice::SwapTo(requested_asset.clone()),
+            AssetList::new(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_vault_token_balance_eq(admin.address(), 0u128)
+        .assert_asset_balance_eq(&requested_asset.into(), &robot.vault_zapper_addr, dust)
+        .assert_asset_balance_eq(
+            &robot.deps.pool_assets[0].clone().into(),
+            &robot.vault_zapper_addr,
+            0u128,
+        );
+}
+
+#[test]
+fn redeem_without_min_out_emits_enforce_min_out_event() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    robot.zapper_deposit(
+        vec![Asset::new(
+            robot.deps.pool_assets[0].clone(),
+            Uint128::new(1000000),
+        )]
+        .into(),
+        None,
+        Uint128::one(),
+        Unwrap::Ok,
+        &admin,
+    );
+
+    // Indexers rely on the EnforceMinOut callback of a single redemption even if
+    // there is no minimum amount to enforce
+    let balance = robot.query_vault_token_balance(admin.address());
+    let res = robot
+        .wasm()
+        .execute(
+            &robot.vault_zapper_addr,
+            &ExecuteMsg::Redeem {
+                vault_address: robot.deps.vault_robot.vault_addr(),
+                recipient: None,
+                receive_choice: ReceiveChoice::SwapTo(robot.deps.pool_assets[1].clone()),
+                min_out: AssetListUnchecked::default(),
+                referral: None,
+                leg_min_outs: None,
+            },
+            &[coin(balance.u128(), robot.deps.vault_robot.vault_token())],
+            &admin,
+        )
+        .unwrap();
+    assert!(res
+        .events
+        .iter()
+        .any(|e| e.ty == "wasm-apollo/vault-zapper/callback_enforce_min_out"));
+}
@@ -0,0 +1,292 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use common::setup_with_second_vault;
+use cosmwasm_std::{coin, Addr, Uint128};
+use cw_it::helpers::Unwrap;
+use cw_it::robot::TestRobot;
+use cw_it::test_tube::Account;
+use cw_it::OwnedTestRunner;
+use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
+use vault_zapper::msg::{ExecuteMsg, ReceiveChoice};
+
+pub mod common;
+
+const LOCK_DURATION: u64 = 300;
+
+#[test]
+fn withdraw_unlocked_many_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, LOCK_DURATION);
+    let other_user = common::VaultZapperRobot::default_account(&runner);
+
+    // Create two unlocking positions in the first vault and one in the second
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_deposit_to_vault(
+            vec![Asset::new(
+                AssetInfo::native("uastro"),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            &second_vault.vault_addr(),
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    robot
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin)
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin)
+        .zapper_unlock_from_vault(
+            &second_vault.vault_addr(),
+            &[coin(
+                second_vault
+                    .query_vault_token_balance(admin.address())
+                    .u128(),
+                second_vault.vault_token(),
+            )],
+            &admin,
+        );
+
+    let positions = vec![
+        (robot.vault_addr(), 0),
+        (robot.vault_addr(), 1),
+        (second_vault.vault_addr(), 0),
+    ];
+    let withdraw_msg = |min_out: Uint128| ExecuteMsg::WithdrawUnlockedMany {
+        positions: positions.clone(),
+        recipient: None,
+        receive_choice: ReceiveChoice::SwapTo(AssetInfo::native("untrn")),
+        min_out: AssetList::from(vec![Asset::new(AssetInfo::native("untrn"), min_out)]).into(),
+    };
+
+    // Positions can not be withdrawn before they have finished unlocking, and
+    // only by their owner
+    robot
+        .zapper_execute(
+            &withdraw_msg(Uint128::one()),
+            &[],
+            Unwrap::Err("Claim has not yet matured"),
+            &admin,
+        )
+        .increase_time(LOCK_DURATION)
+        .zapper_execute(
+            &withdraw_msg(Uint128::one()),
+            &[],
+            Unwrap::Err("Unauthorized"),
+            &other_user,
+        );
+
+    // The minimum amount is enforced on the combined assets
+    let balance_before = robot.query_native_token_balance(admin.address(), "untrn");
+    robot
+        .zapper_execute(
+            &withdraw_msg(Uint128::new(10_000_000)),
+            &[],
+            Unwrap::Err("Minimum amount not met"),
+            &admin,
+        )
+        .zapper_execute(&withdraw_msg(Uint128::one()), &[], Unwrap::Ok, &admin)
+        .assert_zapper_has_unlocking_positions(&admin.address(), &[]);
+    assert!(robot.query_native_token_balance(admin.address(), "untrn") > balance_before);
+    assert!(robot
+        .zapper_query_user_unlocking_positions(&admin.address(), None, None, None)
+        .is_empty());
+
+    // The positions can not be withdrawn again
+    robot.zapper_execute(
+        &withdraw_msg(Uint128::one()),
+        &[],
+        Unwrap::Err("Unauthorized"),
+        &admin,
+    );
+}
+
+#[test]
+fn withdraw_all_matured_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, LOCK_DURATION);
+
+    // Create an unlocking position in each vault, and another one in the first
+    // vault half way through the lock duration
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_deposit_to_vault(
+            vec![Asset::new(
+                AssetInfo::native("uastro"),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            &second_vault.vault_addr(),
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    robot
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin)
+        .zapper_unlock_from_vault(
+            &second_vault.vault_addr(),
+            &[coin(
+                second_vault
+                    .query_vault_token_balance(admin.address())
+                    .u128(),
+                second_vault.vault_token(),
+            )],
+            &admin,
+        )
+        .increase_time(LOCK_DURATION / 2)
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin);
+
+    let withdraw_msg = |vault_address: Option<String>| ExecuteMsg::WithdrawAllMatured {
+        vault_address,
+        recipient: None,
+        receive_choice: ReceiveChoice::Underlying,
+        min_out: AssetList::new().into(),
+        start_after_vault_addr: None,
+        start_after_id: None,
+        limit: None,
+    };
+
+    // Nothing has matured yet
+    robot
+        .zapper_execute(
+            &withdraw_msg(None),
+            &[],
+            Unwrap::Err("No matured unlocking positions"),
+            &admin,
+        )
+        .increase_time(LOCK_DURATION / 2);
+
+    // Only withdraw the matured position of the first vault
+    robot.zapper_execute(
+        &withdraw_msg(Some(robot.vault_addr())),
+        &[],
+        Unwrap::Ok,
+        &admin,
+    );
+    let positions = robot.zapper_query_user_unlocking_positions(&admin.address(), None, None, None);
+    let first_vault_ids: Vec<u64> = positions[&Addr::unchecked(robot.vault_addr())]
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(first_vault_ids, vec![1]);
+    assert_eq!(
+        positions[&Addr::unchecked(second_vault.vault_addr())].len(),
+        1
+    );
+
+    // Withdraw the matured position of the second vault. The position of the
+    // first vault that is still unlocking is left untouched.
+    robot.zapper_execute(&withdraw_msg(None), &[], Unwrap::Ok, &admin);
+    let positions = robot.zapper_query_user_unlocking_positions(&admin.address(), None, None, None);
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[&Addr::unchecked(robot.vault_addr())][0].id, 1);
+
+    // Withdraw the last position once it has matured
+    robot
+        .increase_time(LOCK_DURATION / 2)
+        .zapper_execute(&withdraw_msg(None), &[], Unwrap::Ok, &admin)
+        .assert_zapper_has_unlocking_positions(&admin.address(), &[]);
+}
+
+#[test]
+fn withdraw_all_matured_is_paginated() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, second_vault, admin) = setup_with_second_vault(&runner, LOCK_DURATION);
+
+    // Create two unlocking positions in the first vault and one in the second
+    let vault_token_balance = robot
+        .zapper_deposit(
+            vec![Asset::new(AssetInfo::native("uaxl"), Uint128::new(1000000))].into(),
+            None,
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .zapper_deposit_to_vault(
+            vec![Asset::new(
+                AssetInfo::native("uastro"),
+                Uint128::new(1000000),
+            )]
+            .into(),
+            None,
+            &second_vault.vault_addr(),
+            Uint128::one(),
+            Unwrap::Ok,
+            &admin,
+        )
+        .query_vault_token_balance(admin.address());
+    robot
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin)
+        .zapper_unlock(vault_token_balance.u128() / 2, &admin)
+        .zapper_unlock_from_vault(
+            &second_vault.vault_addr(),
+            &[coin(
+                second_vault
+                    .query_vault_token_balance(admin.address())
+                    .u128(),
+                second_vault.vault_token(),
+            )],
+            &admin,
+        )
+        .increase_time(LOCK_DURATION);
+
+    let withdraw_msg = |vault_address: Option<String>,
+                        start_after_id: Option<u64>,
+                        limit: Option<u32>| ExecuteMsg::WithdrawAllMatured {
+        vault_address,
+        recipient: None,
+        receive_choice: ReceiveChoice::Underlying,
+        min_out: AssetList::new().into(),
+        start_after_vault_addr: None,
+        start_after_id,
+        limit,
+    };
+    let count_positions = || {
+        robot
+            .zapper_query_user_unlocking_positions(&admin.address(), None, None, None)
+            .values()
+            .map(|positions| positions.len())
+            .sum::<usize>()
+    };
+
+    // Only the positions after the given lockup id are withdrawn
+    robot.zapper_execute(
+        &withdraw_msg(Some(robot.vault_addr()), Some(0), None),
+        &[],
+        Unwrap::Ok,
+        &admin,
+    );
+    let positions = robot.zapper_query_user_unlocking_positions(&admin.address(), None, None, None);
+    let first_vault_ids: Vec<u64> = positions[&Addr::unchecked(robot.vault_addr())]
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(first_vault_ids, vec![0]);
+    assert_eq!(count_positions(), 2);
+
+    // Only `limit` positions are withdrawn
+    robot.zapper_execute(&withdraw_msg(None, None, Some(1)), &[], Unwrap::Ok, &admin);
+    assert_eq!(count_positions(), 1);
+
+    robot
+        .zapper_execute(&withdraw_msg(None, None, None), &[], Unwrap::Ok, &admin)
+        .assert_zapper_has_unlocking_positions(&admin.address(), &[]);
+}
@@ -7,7 +7,7 @@ use cw_it::test_tube::Account;
 use cw_it::OwnedTestRunner;
 use cw_vault_standard_test_helpers::traits::CwVaultStandardRobot;
 use test_case::test_case;
-use vault_zapper::msg::ReceiveChoice;
+use vault_zapper::msg::{Cw20HookMsg, ReceiveChoice};
 
 pub mod common;
 
@@ -311,3 +311,43 @@ fn zap_base_tokens_to_both_assets_of_pool() {
         .assert_asset_balance_approx_eq(asset1, &admin.address(), asset1_balance, max_rel_diff)
         .assert_asset_balance_approx_eq(asset2, &admin.address(), asset2_balance, max_rel_diff);
 }
+
+#[test]
+fn zap_base_tokens_via_cw20_hook_works() {
+    let owned_runner: OwnedTestRunner = common::get_test_runner();
+    let runner = owned_runner.as_ref();
+    let (robot, admin) = setup(&runner, 0);
+
+    // The base token of the vault is a CW20, so it can be sent with a hook msg
+    let base_token_addr = match robot.deps.vault_pool.lp_token() {
+        AssetInfo::Cw20(addr) => addr.to_string(),
+        AssetInfo::Native(_) => panic!("Base token is not a CW20"),
+    };
+    let base_token_balance = robot.query_base_token_balance(admin.address());
+    let zap_amount = Uint128::new(1000000);
+    let pool_assets = robot.deps.pool_assets.clone();
+    let balances_before: Vec<Uint128> = pool_assets
+        .iter()
+        .map(|info| robot.query_asset_balance(&info.clone().into(), &admin.address()))
+        .collect();
+
+    robot
+        .zapper_cw20_send(
+            &base_token_addr,
+            zap_amount,
+            &Cw20HookMsg::ZapBaseTokens {
+                recipient: None,
+                receive_choice: ReceiveChoice::Underlying,
+                min_out: AssetList::new().into(),
+                referral: None,
+                leg_min_outs: None,
+            },
+            Unwrap::Ok,
+            &admin,
+        )
+        .assert_base_token_balance_eq(admin.address(), base_token_balance - zap_amount);
+
+    for (info, balance_before) in pool_assets.iter().zip(balances_before) {
+        robot.assert_asset_balance_gt(&info.clone().into(), &admin.address(), balance_before);
+    }
+}